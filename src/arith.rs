@@ -0,0 +1,311 @@
+//! Arithmetic operators and concatenation of values, and aggregates over
+//! tables.
+
+use std::convert::TryFrom;
+use std::fmt::Write;
+use std::ops::{ Add, Div, Mul, Sub };
+
+use { Number, Table, Value, ValueKind };
+
+#[derive(Copy, Clone)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+fn apply(a: &Value, b: &Value, op: Op) -> Value {
+    if let (Op::Add, &Value::String(ref x), &Value::String(ref y)) = (op, a, b) {
+        let mut text = String::with_capacity(x.len() + y.len());
+        text.push_str(x);
+        text.push_str(y);
+        return Value::from(text);
+    }
+    match (Number::of(a), Number::of(b)) {
+        (Some(Number::Int(x)), Some(Number::Int(y))) => {
+            let result = match op {
+                Op::Add => x.checked_add(y),
+                Op::Sub => x.checked_sub(y),
+                Op::Mul => x.checked_mul(y),
+                Op::Div if y == 0 => None,
+                Op::Div if x % y != 0 => return Value::f64(x as f64 / y as f64),
+                Op::Div => x.checked_div(y),
+            };
+            let usize_only = a.kind() == ValueKind::Usize && b.kind() == ValueKind::Usize;
+            let signed = a.kind() == ValueKind::I64 || b.kind() == ValueKind::I64;
+            result.map_or(Value::Null, |x| integer(x, usize_only, signed))
+        }
+        (Some(x), Some(y)) => {
+            let (x, y) = (x.to_f64(), y.to_f64());
+            Value::f64(match op {
+                Op::Add => x + y,
+                Op::Sub => x - y,
+                Op::Mul => x * y,
+                Op::Div => x / y,
+            })
+        }
+        _ => Value::Null,
+    }
+}
+
+/// Stores an integer result in the variant promoted from the operands.
+///
+/// Operands that are all `Usize` give `Usize`, operands without `I64` give
+/// `U64` and others give `I64`. If the result does not fit, the other
+/// integer variants are tried before giving up with `Null`.
+fn integer(x: i128, usize_only: bool, signed: bool) -> Value {
+    if usize_only {
+        if let Ok(x) = usize::try_from(x) {
+            return Value::Usize(x);
+        }
+    }
+    if !signed {
+        if let Ok(x) = u64::try_from(x) {
+            return Value::U64(x);
+        }
+    }
+    if let Ok(x) = i64::try_from(x) {
+        return Value::I64(x);
+    }
+    u64::try_from(x).map_or(Value::Null, Value::U64)
+}
+
+macro_rules! impl_op {
+    ($trait_:ident, $method:ident, $op:expr) => {
+        impl<'a> $trait_<&'a Value> for &'a Value {
+            type Output = Value;
+
+            fn $method(self, other: &'a Value) -> Value {
+                apply(self, other, $op)
+            }
+        }
+
+        impl $trait_ for Value {
+            type Output = Value;
+
+            fn $method(self, other: Value) -> Value {
+                apply(&self, &other, $op)
+            }
+        }
+    };
+}
+
+impl_op!(Add, add, Op::Add);
+impl_op!(Sub, sub, Op::Sub);
+impl_op!(Mul, mul, Op::Mul);
+impl_op!(Div, div, Op::Div);
+
+impl Value {
+    /// Joins two values of the same kind.
+    ///
+    /// - Two strings give a string with the text of both.
+    /// - Two bytes values give the bytes of both.
+    /// - Two arrays give an array with the elements of both.
+    /// - Two tables give a table with the entries of both. Where both have
+    ///   the same key, the value of `other` wins, like in `Table::merge`.
+    ///
+    /// Any other pair gives `Null`, like arithmetic on incompatible
+    /// values. Chars are not strings here, and array-like tables are
+    /// merged by key, not appended.
+    pub fn concat(&self, other: &Value) -> Value {
+        match (self, other) {
+            (&Value::String(_), &Value::String(_)) => self + other,
+            (&Value::Bytes(ref a), &Value::Bytes(ref b)) => {
+                let mut bytes = Vec::with_capacity(a.len() + b.len());
+                bytes.extend_from_slice(a);
+                bytes.extend_from_slice(b);
+                Value::from(bytes)
+            }
+            (&Value::Array(ref a), &Value::Array(ref b)) => {
+                Value::array(a.iter().chain(b.iter()).cloned().collect())
+            }
+            (&Value::Table(ref a), &Value::Table(ref b)) => {
+                let mut table = (**a).clone();
+                table.merge((**b).clone());
+                Value::table(table)
+            }
+            _ => Value::Null,
+        }
+    }
+
+    /// Joins values into one string, with a separator between them.
+    ///
+    /// Each value is written as its `Display` form, so strings and chars
+    /// appear without quotes, bytes as base64, and `Null` as `null`.
+    ///
+    /// ```
+    /// use table::Value;
+    ///
+    /// let parts = [Value::from("x"), Value::from(1usize), Value::from('c')];
+    /// assert_eq!(Value::join(&parts, ", "), Value::from("x, 1, c"));
+    /// ```
+    pub fn join(values: &[Value], sep: &str) -> Value {
+        let mut text = String::new();
+        for (i, val) in values.iter().enumerate() {
+            if i > 0 { text.push_str(sep); }
+            write!(text, "{}", val).unwrap();
+        }
+        Value::from(text)
+    }
+}
+
+impl Table {
+    /// Adds up the numeric values, skipping values that are not numbers.
+    ///
+    /// Integers are summed exactly and stored in the variant that `+` would
+    /// give for them, or `Null` if the sum does not fit any integer variant.
+    /// If any value is a float, the sum is `F64`. An empty table, or one
+    /// without numbers, sums to `Usize(0)`.
+    pub fn sum_values(&self) -> Value {
+        let mut int_sum: Option<i128> = Some(0);
+        let mut float_sum = None;
+        let (mut usize_only, mut signed) = (true, false);
+        for val in self.values() {
+            match Number::of(val) {
+                Some(Number::Int(x)) => {
+                    int_sum = int_sum.and_then(|sum| sum.checked_add(x));
+                }
+                Some(Number::Float(x)) => float_sum = Some(float_sum.unwrap_or(0.0) + x),
+                None => continue,
+            }
+            usize_only &= val.kind() == ValueKind::Usize;
+            signed |= val.kind() == ValueKind::I64;
+        }
+        match (int_sum, float_sum) {
+            (Some(x), Some(y)) => Value::f64(x as f64 + y),
+            (Some(x), None) => integer(x, usize_only, signed),
+            (None, _) => Value::Null,
+        }
+    }
+
+    /// Returns the smallest numeric value, or `Null` if there is none.
+    ///
+    /// Values that are not numbers are skipped. Numbers compare by value,
+    /// and among equal numbers the one with the smallest key is returned.
+    pub fn min_values(&self) -> Value {
+        self.numbers()
+            .min_by(|a, b| a.1.cmp(b.1).then(a.0.cmp(b.0)))
+            .map_or(Value::Null, |(_, val)| val.clone())
+    }
+
+    /// Returns the largest numeric value, or `Null` if there is none.
+    ///
+    /// Values that are not numbers are skipped, and NaN counts as larger
+    /// than every other number. Among equal numbers the one with the
+    /// smallest key is returned.
+    pub fn max_values(&self) -> Value {
+        self.numbers()
+            .max_by(|a, b| a.1.cmp(b.1).then(b.0.cmp(a.0)))
+            .map_or(Value::Null, |(_, val)| val.clone())
+    }
+
+    /// Returns the mean of the numeric values, or `None` if there is none.
+    ///
+    /// Values that are not numbers are skipped.
+    pub fn mean(&self) -> Option<f64> {
+        let (count, sum) = self.numbers().fold((0usize, 0.0), |(count, sum), entry| {
+            (count + 1, sum + Number::of(entry.1).unwrap().to_f64())
+        });
+        if count == 0 { None } else { Some(sum / count as f64) }
+    }
+
+    fn numbers(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.iter().filter(|&(_, val)| Number::of(val).is_some())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_integer_arithmetic() {
+        assert_eq!(Value::Usize(2) + Value::Usize(3), Value::Usize(5));
+        assert!(matches!(Value::Usize(2) + Value::Usize(3), Value::Usize(_)));
+        assert!(matches!(Value::Usize(2) * Value::U64(3), Value::U64(6)));
+        assert!(matches!(Value::Usize(2) - Value::Usize(3), Value::I64(-1)));
+        assert!(matches!(Value::I64(-2) + Value::U64(u64::MAX), Value::U64(_)));
+        assert!(matches!(Value::Usize(6) / Value::I64(3), Value::I64(2)));
+        assert!(matches!(Value::Usize(7) / Value::Usize(2), Value::F64(_)));
+        assert_eq!(Value::Usize(7) / Value::Usize(2), Value::f64(3.5));
+        assert!(matches!(Value::Usize(1) / Value::Usize(0), Value::Null));
+        assert!(matches!(Value::U64(u64::MAX) * Value::U64(2), Value::Null));
+        assert!(matches!(Value::I64(i64::MIN) - Value::I64(1), Value::Null));
+    }
+
+    #[test]
+    fn test_concat() {
+        assert_eq!(Value::str("ab").concat(&Value::str("cd")), Value::str("abcd"));
+        assert_eq!(Value::from(&b"a"[..]).concat(&Value::from(&b"b"[..])),
+            Value::from(&b"ab"[..]));
+        let a = Value::array(vec![Value::Usize(1)]);
+        let b = Value::array(vec![Value::Null, Value::str("x")]);
+        assert_eq!(a.concat(&b), Value::array(vec![Value::Usize(1), Value::Null,
+            Value::str("x")]));
+        let a = Value::table(table!{ "x" => 1usize, "y" => 2usize });
+        let b = Value::table(table!{ "y" => 3usize, "z" => 4usize });
+        assert_eq!(a.concat(&b), Value::table(table!{
+            "x" => 1usize, "y" => 3usize, "z" => 4usize,
+        }));
+
+        assert_eq!(Value::str("a").concat(&Value::Char('b')), Value::Null);
+        assert_eq!(Value::str("a").concat(&Value::Usize(1)), Value::Null);
+        assert_eq!(a.concat(&Value::array(vec![])), Value::Null);
+        assert_eq!(Value::Null.concat(&Value::Null), Value::Null);
+    }
+
+    #[test]
+    fn test_join() {
+        let parts = [Value::str("a"), Value::Usize(1), Value::Char('c'), Value::Null,
+            Value::f64(1.5), Value::Bool(true)];
+        assert_eq!(Value::join(&parts, "-"), Value::str("a-1-c-null-1.5-true"));
+        assert_eq!(Value::join(&parts[..1], ", "), Value::str("a"));
+        assert_eq!(Value::join(&[], ", "), Value::str(""));
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let a = table!{ "a" => 1usize, "b" => 2usize, "c" => "x", "d" => Value::Null };
+        assert!(matches!(a.sum_values(), Value::Usize(3)));
+        assert_eq!(a.min_values(), Value::Usize(1));
+        assert_eq!(a.max_values(), Value::Usize(2));
+        assert_eq!(a.mean(), Some(1.5));
+
+        let b = table!{ "a" => 1usize, "b" => -4i64, "c" => 0.5, "d" => 2u64 };
+        assert!(matches!(b.sum_values(), Value::F64(_)));
+        assert_eq!(b.sum_values(), Value::f64(-0.5));
+        assert_eq!(b.min_values(), Value::I64(-4));
+        assert_eq!(b.max_values(), Value::U64(2));
+        assert_eq!(b.mean(), Some(-0.125));
+
+        let c = table!{ "a" => 3usize, "b" => -4i64, "c" => u64::MAX };
+        assert!(matches!(c.sum_values(), Value::U64(_)));
+        assert_eq!(c.sum_values(), Value::U64(u64::MAX - 1));
+        let d = table!{ "a" => i64::MIN, "b" => i64::MIN };
+        assert!(matches!(d.sum_values(), Value::Null));
+
+        let e = table!{ "a" => 1.0, "b" => 1usize };
+        assert!(matches!(e.min_values(), Value::F64(_)));
+        assert!(matches!(e.max_values(), Value::F64(_)));
+
+        let empty = table!{ "a" => "x" };
+        for table in &[Table::new(), empty] {
+            assert!(matches!(table.sum_values(), Value::Usize(0)));
+            assert_eq!(table.min_values(), Value::Null);
+            assert_eq!(table.max_values(), Value::Null);
+            assert_eq!(table.mean(), None);
+        }
+    }
+
+    #[test]
+    fn test_float_and_string_arithmetic() {
+        assert!(matches!(Value::Usize(1) + Value::f64(0.5), Value::F64(_)));
+        assert_eq!(&Value::Usize(1) + &Value::f64(0.5), Value::f64(1.5));
+        assert_eq!(Value::f64(1.0) / Value::f64(0.0), Value::f64(f64::INFINITY));
+        assert_eq!(Value::str("ab") + Value::str("cd"), Value::str("abcd"));
+        assert!((Value::str("ab") - Value::str("b")).is_null());
+        assert!((Value::str("1") + Value::Usize(1)).is_null());
+        assert!((Value::Bool(true) * Value::Usize(1)).is_null());
+    }
+}
@@ -0,0 +1,68 @@
+//! Fluent construction of tables.
+
+use { Table, Value };
+
+/// Builds a table by chaining insertions.
+///
+/// ```
+/// use table::TableBuilder;
+///
+/// let player = TableBuilder::new()
+///     .set("name", "Sven")
+///     .set("level", 3u64)
+///     .set_table("pos", |b| b.set(0usize, 1.0).set(1usize, 2.0))
+///     .build();
+/// assert_eq!(player["level"], table::Value::U64(3));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct TableBuilder {
+    table: Table,
+}
+
+impl TableBuilder {
+    /// Creates a builder for an empty table.
+    pub fn new() -> TableBuilder {
+        TableBuilder { table: Table::new() }
+    }
+
+    /// Sets an entry, overwriting any previous value under the key.
+    pub fn set<K, V>(mut self, key: K, val: V) -> TableBuilder
+        where K: Into<Value>, V: Into<Value>
+    {
+        self.table.insert(key.into(), val.into());
+        self
+    }
+
+    /// Sets an entry to a nested table built by a closure.
+    pub fn set_table<K, F>(self, key: K, f: F) -> TableBuilder
+        where K: Into<Value>, F: FnOnce(TableBuilder) -> TableBuilder
+    {
+        self.set(key, f(TableBuilder::new()).build())
+    }
+
+    /// Returns the built table.
+    pub fn build(self) -> Table {
+        self.table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder() {
+        let a = TableBuilder::new()
+            .set("name", "Sven")
+            .set("level", 3u64)
+            .set_table("pos", |b| b.set(0usize, 1.0).set(1usize, 2.0))
+            .set("level", 4u64)
+            .build();
+        assert_eq!(a, table!{
+            "name" => "Sven",
+            "level" => 4u64,
+            "pos" => table!{ 0usize => 1.0, 1usize => 2.0 },
+        });
+        assert_eq!(TableBuilder::new().build(), Table::new());
+    }
+}
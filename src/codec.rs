@@ -0,0 +1,189 @@
+//! Text encodings for binary data.
+//!
+//! Base64 and hex are implemented here, so they need no dependency.
+
+use std::error::Error;
+use std::fmt;
+
+use Value;
+
+const BASE64: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+const HEX: &[u8; 16] = b"0123456789abcdef";
+
+/// An error returned when decoding base64 or hex fails.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DecodeError {
+    /// The byte offset where the error was detected.
+    pub offset: usize,
+    /// A description of what went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl Error for DecodeError {}
+
+impl Value {
+    /// Encodes bytes or a string as standard base64 with padding.
+    ///
+    /// Strings are encoded as their UTF-8 bytes. Returns `None` for other
+    /// kinds of values.
+    pub fn to_base64(&self) -> Option<String> {
+        self.encoded_bytes().map(encode_base64)
+    }
+
+    /// Decodes standard base64 into a bytes value.
+    ///
+    /// Padding is optional, but if present the text must have a length
+    /// that is a multiple of 4. Whitespace is not allowed.
+    pub fn from_base64(text: &str) -> Result<Value, DecodeError> {
+        decode_base64(text).map(Value::from)
+    }
+
+    /// Encodes bytes or a string as lowercase hex.
+    ///
+    /// Strings are encoded as their UTF-8 bytes. Returns `None` for other
+    /// kinds of values.
+    pub fn to_hex(&self) -> Option<String> {
+        self.encoded_bytes().map(encode_hex)
+    }
+
+    /// Decodes hex with digits in either case into a bytes value.
+    pub fn from_hex(text: &str) -> Result<Value, DecodeError> {
+        decode_hex(text).map(Value::from)
+    }
+
+    fn encoded_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::Bytes(ref bytes) => Some(bytes),
+            Value::String(ref text) => Some(text.as_bytes()),
+            _ => None,
+        }
+    }
+}
+
+fn error(offset: usize, message: &str) -> DecodeError {
+    DecodeError { offset, message: message.into() }
+}
+
+/// Encodes bytes as standard base64 with padding.
+pub fn encode_base64(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(BASE64[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+fn decode_base64(text: &str) -> Result<Vec<u8>, DecodeError> {
+    let bytes = text.as_bytes();
+    let data = text.trim_end_matches('=');
+    let padding = text.len() - data.len();
+    if padding > 2 || (padding > 0 && !text.len().is_multiple_of(4)) {
+        return Err(error(data.len(), "invalid padding"));
+    }
+    if data.len() % 4 == 1 {
+        return Err(error(data.len(), "truncated input"));
+    }
+
+    let mut out = Vec::with_capacity(data.len() * 3 / 4);
+    let mut n: u32 = 0;
+    for (i, &b) in bytes[..data.len()].iter().enumerate() {
+        let digit = match BASE64.iter().position(|&c| c == b) {
+            Some(digit) => digit as u32,
+            None => return Err(error(i, "invalid base64 character")),
+        };
+        n = n << 6 | digit;
+        if i % 4 == 3 {
+            out.extend_from_slice(&[(n >> 16) as u8, (n >> 8) as u8, n as u8]);
+            n = 0;
+        }
+    }
+    match data.len() % 4 {
+        2 => out.push((n >> 4) as u8),
+        3 => out.extend_from_slice(&[(n >> 10) as u8, (n >> 2) as u8]),
+        _ => {}
+    }
+    Ok(out)
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for &b in bytes {
+        out.push(HEX[(b >> 4) as usize] as char);
+        out.push(HEX[(b & 0xf) as usize] as char);
+    }
+    out
+}
+
+fn decode_hex(text: &str) -> Result<Vec<u8>, DecodeError> {
+    if !text.len().is_multiple_of(2) {
+        return Err(error(text.len(), "odd number of hex digits"));
+    }
+    let digit = |i: usize| {
+        (text.as_bytes()[i] as char).to_digit(16)
+            .ok_or_else(|| error(i, "invalid hex digit"))
+    };
+    (0..text.len() / 2).map(|i| {
+        Ok((digit(2 * i)? << 4 | digit(2 * i + 1)?) as u8)
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_base64() {
+        assert_eq!(encode_base64(b""), "");
+        assert_eq!(encode_base64(b"f"), "Zg==");
+        assert_eq!(encode_base64(b"fo"), "Zm8=");
+        assert_eq!(encode_base64(b"foo"), "Zm9v");
+        assert_eq!(encode_base64(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode_base64(&[0xff, 0xfe]), "//4=");
+    }
+
+    #[test]
+    fn test_base64() {
+        for text in &["", "f", "fo", "foo", "foob", "fooba", "foobar"] {
+            let val = Value::str(text);
+            let encoded = val.to_base64().unwrap();
+            assert_eq!(Value::from_base64(&encoded), Ok(Value::from(text.as_bytes())));
+            let unpadded = encoded.trim_end_matches('=');
+            assert_eq!(Value::from_base64(unpadded), Ok(Value::from(text.as_bytes())));
+        }
+        assert_eq!(Value::from(vec![0xffu8, 0xfe]).to_base64(), Some("//4=".into()));
+        assert_eq!(Value::Usize(1).to_base64(), None);
+        assert_eq!(Value::from_base64("Zm9v!A"),
+            Err(error(4, "invalid base64 character")));
+        assert_eq!(Value::from_base64("Zm9vY").unwrap_err().message, "truncated input");
+        assert_eq!(Value::from_base64("Zg=").unwrap_err().message, "invalid padding");
+        assert_eq!(Value::from_base64("Z===").unwrap_err().message, "invalid padding");
+    }
+
+    #[test]
+    fn test_hex() {
+        let val = Value::from(vec![0x00u8, 0x7f, 0xab]);
+        assert_eq!(val.to_hex(), Some("007fab".into()));
+        assert_eq!(Value::from_hex("007FaB"), Ok(val));
+        assert_eq!(Value::str("hi").to_hex(), Some("6869".into()));
+        assert_eq!(Value::Null.to_hex(), None);
+        assert_eq!(Value::from_hex(""), Ok(Value::from(Vec::<u8>::new())));
+        assert_eq!(Value::from_hex("abc"), Err(error(3, "odd number of hex digits")));
+        assert_eq!(Value::from_hex("0g"), Err(error(1, "invalid hex digit")));
+    }
+}
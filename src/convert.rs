@@ -0,0 +1,321 @@
+//! Extensible conversion from values into other types.
+
+use std::convert::TryFrom;
+use std::sync::Arc;
+
+use { Number, Table, Value, ValueError, ValueKind };
+
+/// Converts a value into another type, failing if the kinds do not match.
+///
+/// This is implemented for the primitive types, `String`, `Table` and
+/// `Value` itself, and for `Vec<T>` and `Option<T>` of types that
+/// implement it. Implement it for your own types to convert them with
+/// `Value::try_into_typed`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate table;
+///
+/// use table::{ FromValue, Value, ValueError, ValueKind };
+///
+/// struct Point {
+///     x: f64,
+///     y: f64,
+/// }
+///
+/// impl FromValue for Point {
+///     fn from_value(val: Value) -> Result<Point, ValueError> {
+///         let table = match val.as_table() {
+///             Some(table) => table,
+///             None => {
+///                 let found = val.kind();
+///                 return Err(ValueError { expected: ValueKind::Table, found });
+///             }
+///         };
+///         Ok(Point {
+///             x: table["x"].clone().try_into_typed()?,
+///             y: table["y"].clone().try_into_typed()?,
+///         })
+///     }
+/// }
+///
+/// # fn main() {
+/// let val = Value::from(table!{ "x" => 1.0, "y" => 2.0 });
+/// let point: Point = val.try_into_typed().unwrap();
+/// assert_eq!((point.x, point.y), (1.0, 2.0));
+/// # }
+/// ```
+pub trait FromValue: Sized {
+    /// Converts the value, or returns the expected and found kinds.
+    fn from_value(val: Value) -> Result<Self, ValueError>;
+}
+
+impl Value {
+    /// Converts the value into any type that implements `FromValue`.
+    pub fn try_into_typed<T: FromValue>(self) -> Result<T, ValueError> {
+        T::from_value(self)
+    }
+
+    /// Converts `Null` to `None` and other values to `Some`.
+    ///
+    /// This undoes `From<Option<T>> for Value`. Returns an error if the
+    /// value is neither `Null` nor convertible to `T`.
+    pub fn into_option<T: FromValue>(self) -> Result<Option<T>, ValueError> {
+        Option::<T>::from_value(self)
+    }
+}
+
+impl Value {
+    /// Converts the value into another kind where there is a sensible
+    /// reading of it, like a scripting language would.
+    ///
+    /// A value always coerces to its own kind. The other supported pairs:
+    ///
+    /// | Target | Source | Result |
+    /// |---|---|---|
+    /// | `Bool` | `Null` | `false` |
+    /// | `Bool` | number | `false` for zero, `true` otherwise except NaN |
+    /// | `Bool` | string | `true` and `false` only |
+    /// | integer | other integer | the same number, if it fits |
+    /// | integer | `F64` | a whole number that fits |
+    /// | integer | `Bool` | `0` or `1` |
+    /// | integer | string | parsed like `parse_typed`, then as a number |
+    /// | `F64` | number | the nearest float |
+    /// | `F64` | `Bool` | `0.0` or `1.0` |
+    /// | `F64` | string | parsed like `parse_typed`, then as a number |
+    /// | `Char` | string | the character of a one-character string |
+    /// | `String` | `Null`, `Bool`, number or `Char` | the `Display` form |
+    /// | `String` | `Bytes` | the bytes, if they are valid UTF-8 |
+    /// | `Bytes` | string | the UTF-8 bytes |
+    /// | `Array` | `Table` | the values, if the table is an array |
+    /// | `Table` | `Array` | the elements keyed by `Usize` index |
+    ///
+    /// All other pairs, including any coercion to `Null`, return `None`.
+    pub fn coerce_to(&self, kind: ValueKind) -> Option<Value> {
+        if self.kind() == kind {
+            return Some(self.clone());
+        }
+        match kind {
+            ValueKind::Null => None,
+            ValueKind::Bool => match *self {
+                Value::Null => Some(Value::Bool(false)),
+                Value::String(ref text) => match &**text {
+                    "true" => Some(Value::Bool(true)),
+                    "false" => Some(Value::Bool(false)),
+                    _ => None,
+                },
+                _ => {
+                    let x = self.as_number()?;
+                    if x.is_nan() { None } else { Some(Value::Bool(x != 0.0)) }
+                }
+            },
+            ValueKind::Usize => self.coerce_integer()
+                .and_then(|x| usize::try_from(x).ok()).map(Value::Usize),
+            ValueKind::U64 => self.coerce_integer()
+                .and_then(|x| u64::try_from(x).ok()).map(Value::U64),
+            ValueKind::I64 => self.coerce_integer()
+                .and_then(|x| i64::try_from(x).ok()).map(Value::I64),
+            ValueKind::F64 => self.coerce_number().map(|x| Value::f64(x.to_f64())),
+            ValueKind::Char => {
+                let mut chars = self.as_str()?.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(ch), None) => Some(Value::Char(ch)),
+                    _ => None,
+                }
+            }
+            ValueKind::String => match *self {
+                Value::Bytes(ref bytes) => {
+                    ::std::str::from_utf8(bytes).ok().map(Value::str)
+                }
+                Value::Null | Value::Bool(_) | Value::Usize(_) | Value::U64(_) |
+                Value::I64(_) | Value::F64(_) | Value::Char(_) => {
+                    Some(Value::from(self.to_string()))
+                }
+                _ => None,
+            },
+            ValueKind::Bytes => self.as_str().map(|text| Value::from(text.as_bytes())),
+            ValueKind::Array => self.as_table()?.to_vec().map(Value::from),
+            ValueKind::Table => {
+                let items = self.as_array()?;
+                Some(Value::table(items.iter().cloned().enumerate().collect()))
+            }
+        }
+    }
+
+    fn coerce_number(&self) -> Option<Number> {
+        match *self {
+            Value::Bool(x) => Some(Number::Int(x as i128)),
+            Value::String(ref text) => Number::of(&Value::parse_typed(text)),
+            _ => Number::of(self),
+        }
+    }
+
+    fn coerce_integer(&self) -> Option<i128> {
+        match self.coerce_number()? {
+            Number::Int(x) => Some(x),
+            Number::Float(x) => {
+                let range = i64::MIN as f64..u64::MAX as f64;
+                if x.fract() != 0.0 || !range.contains(&x) {
+                    return None;
+                }
+                Some(x as i128)
+            }
+        }
+    }
+}
+
+macro_rules! from_value_via_try_from {
+    ($($ty:ty),*) => {$(
+        impl FromValue for $ty {
+            fn from_value(val: Value) -> Result<$ty, ValueError> {
+                <$ty>::try_from(val)
+            }
+        }
+    )*}
+}
+
+from_value_via_try_from!(usize, u64, i64, f64, bool, char, String);
+
+impl FromValue for Value {
+    fn from_value(val: Value) -> Result<Value, ValueError> {
+        Ok(val)
+    }
+}
+
+impl FromValue for Table {
+    fn from_value(val: Value) -> Result<Table, ValueError> {
+        match val {
+            Value::Table(table) => Ok(unwrap_or_clone(table)),
+            _ => Err(ValueError::new(ValueKind::Table, &val)),
+        }
+    }
+}
+
+/// Converts `Null` to `None` and other values to `Some`.
+impl<T: FromValue> FromValue for Option<T> {
+    fn from_value(val: Value) -> Result<Option<T>, ValueError> {
+        match val {
+            Value::Null => Ok(None),
+            _ => T::from_value(val).map(Some),
+        }
+    }
+}
+
+/// Converts the elements of an array, failing on the first mismatch.
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(val: Value) -> Result<Vec<T>, ValueError> {
+        match val {
+            Value::Array(items) => {
+                unwrap_or_clone(items).into_iter().map(T::from_value).collect()
+            }
+            _ => Err(ValueError::new(ValueKind::Array, &val)),
+        }
+    }
+}
+
+fn unwrap_or_clone<T: Clone>(val: Arc<T>) -> T {
+    Arc::try_unwrap(val).unwrap_or_else(|val| (*val).clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_into_typed() {
+        assert_eq!(Value::Usize(3).try_into_typed::<usize>(), Ok(3));
+        assert_eq!(Value::str("a").try_into_typed::<String>(), Ok("a".to_owned()));
+        assert_eq!(Value::Bool(true).try_into_typed::<bool>(), Ok(true));
+        assert_eq!(Value::Null.try_into_typed::<Value>(), Ok(Value::Null));
+        assert_eq!(Value::from(table!{ "a" => 1 }).try_into_typed::<Table>(),
+            Ok(table!{ "a" => 1 }));
+        assert_eq!(Value::Usize(3).try_into_typed::<bool>(),
+            Err(ValueError { expected: ValueKind::Bool, found: ValueKind::Usize }));
+    }
+
+    #[test]
+    fn test_option() {
+        let mut a = Table::new();
+        let nickname: Option<&str> = None;
+        a["nickname"] = nickname.into();
+        a["level"] = Some(3u64).into();
+        assert_eq!(a, table!{ "nickname" => Value::Null, "level" => 3u64 });
+        assert_eq!(a["nickname"].clone().into_option::<String>(), Ok(None));
+        assert_eq!(a["level"].clone().into_option::<u64>(), Ok(Some(3)));
+        assert_eq!(a["level"].clone().into_option::<String>().unwrap_err().found,
+            ValueKind::U64);
+    }
+
+    #[test]
+    fn test_coerce_to() {
+        use ValueKind::*;
+
+        let cases = [
+            (Value::str("12"), Usize, Some(Value::Usize(12))),
+            (Value::str("-12"), I64, Some(Value::I64(-12))),
+            (Value::str("-12"), U64, None),
+            (Value::str("2.5"), F64, Some(Value::f64(2.5))),
+            (Value::str("2.5"), Usize, None),
+            (Value::str("2.0"), U64, Some(Value::U64(2))),
+            (Value::str("x"), F64, None),
+            (Value::str("true"), Bool, Some(Value::Bool(true))),
+            (Value::str("yes"), Bool, None),
+            (Value::Usize(3), String, Some(Value::str("3"))),
+            (Value::f64(0.5), String, Some(Value::str("0.5"))),
+            (Value::Bool(true), Usize, Some(Value::Usize(1))),
+            (Value::Bool(false), F64, Some(Value::f64(0.0))),
+            (Value::Bool(false), String, Some(Value::str("false"))),
+            (Value::Null, Bool, Some(Value::Bool(false))),
+            (Value::Null, String, Some(Value::str("null"))),
+            (Value::f64(0.0), Bool, Some(Value::Bool(false))),
+            (Value::I64(-2), Bool, Some(Value::Bool(true))),
+            (Value::f64(f64::NAN), Bool, None),
+            (Value::f64(3.0), I64, Some(Value::I64(3))),
+            (Value::f64(1e30), U64, None),
+            (Value::I64(-1), Usize, None),
+            (Value::U64(7), Usize, Some(Value::Usize(7))),
+            (Value::Usize(7), F64, Some(Value::f64(7.0))),
+            (Value::str("ab"), Bytes, Some(Value::from(&b"ab"[..]))),
+            (Value::from(&b"ab"[..]), String, Some(Value::str("ab"))),
+            (Value::from(&[0xffu8][..]), String, None),
+            (Value::str("é"), Char, Some(Value::Char('é'))),
+            (Value::str("ab"), Char, None),
+            (Value::Char('x'), String, Some(Value::str("x"))),
+            (Value::Usize(1), Null, None),
+            (Value::Usize(1), Table, None),
+        ];
+        for &(ref val, kind, ref expected) in cases.iter() {
+            let found = val.coerce_to(kind);
+            assert_eq!(found, *expected, "{:?} to {}", val, kind);
+            if let Some(ref found) = found {
+                assert_eq!(found.kind(), kind);
+            }
+        }
+    }
+
+    #[test]
+    fn test_coerce_containers() {
+        let items = Value::from(vec![Value::str("a"), Value::Usize(2)]);
+        let table = Value::from(table!{ 0usize => "a", 1usize => 2usize });
+        assert_eq!(items.coerce_to(ValueKind::Table), Some(table.clone()));
+        assert_eq!(table.coerce_to(ValueKind::Array), Some(items.clone()));
+        assert_eq!(Value::from(table!{ "a" => 1 }).coerce_to(ValueKind::Array), None);
+        assert_eq!(items.coerce_to(ValueKind::Array), Some(items.clone()));
+        assert_eq!(items.coerce_to(ValueKind::String), None);
+    }
+
+    #[test]
+    fn test_try_into_typed_containers() {
+        assert_eq!(Value::Null.try_into_typed::<Option<u64>>(), Ok(None));
+        assert_eq!(Value::U64(2).try_into_typed::<Option<u64>>(), Ok(Some(2)));
+        let items = Value::from(vec![Value::f64(0.5), Value::f64(1.5)]);
+        assert_eq!(items.try_into_typed::<Vec<f64>>(), Ok(vec![0.5, 1.5]));
+        let items = Value::from(vec![Value::f64(0.5), Value::Null]);
+        assert_eq!(items.clone().try_into_typed::<Vec<Option<f64>>>(),
+            Ok(vec![Some(0.5), None]));
+        assert_eq!(items.try_into_typed::<Vec<f64>>(),
+            Err(ValueError { expected: ValueKind::F64, found: ValueKind::Null }));
+        assert_eq!(Value::Null.try_into_typed::<Vec<f64>>().unwrap_err().expected,
+            ValueKind::Array);
+    }
+}
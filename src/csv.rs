@@ -0,0 +1,236 @@
+//! Reading and writing tables as CSV.
+
+use std::error::Error;
+use std::fmt;
+
+use { Table, Value };
+
+/// An error returned when parsing CSV fails.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct CsvError {
+    /// The line where the error was detected, starting at 1.
+    pub line: usize,
+    /// A description of what went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on line {}", self.message, self.line)
+    }
+}
+
+impl Error for CsvError {}
+
+impl Table {
+    /// Writes the table as CSV, treating each value as a row.
+    ///
+    /// Rows are the table values in key order, usually an array of tables,
+    /// and values that are not tables are skipped. The header holds the
+    /// string keys of all rows, sorted. Missing and `Null` cells are left
+    /// empty. Cells that contain separators, quotes or line breaks are
+    /// quoted, and so are strings that `from_csv` would otherwise read as
+    /// another kind of value.
+    pub fn to_csv(&self) -> String {
+        let rows: Vec<&Table> = self.sorted_entries().into_iter()
+            .filter_map(|(_, val)| val.as_table())
+            .collect();
+        let mut columns: Vec<&Value> = rows.iter()
+            .flat_map(|row| row.keys())
+            .filter(|key| key.is_string())
+            .collect();
+        columns.sort();
+        columns.dedup();
+
+        let mut out = String::new();
+        if columns.is_empty() {
+            return out;
+        }
+        let header: Vec<&str> = columns.iter().map(|key| key.as_str().unwrap()).collect();
+        write_record(&mut out, header.iter().map(|name| write_text(name, true)));
+        for row in rows {
+            write_record(&mut out, columns.iter().map(|key| match row.get(*key) {
+                None | Some(&Value::Null) => String::new(),
+                Some(&Value::String(ref text)) => write_text(text, false),
+                Some(val) => write_text(&val.to_string(), true),
+            }));
+        }
+        out
+    }
+
+    /// Parses CSV with a header row into a table of rows.
+    ///
+    /// Rows are keyed by `Usize` indices starting at 0 and map the header
+    /// names to cells. Missing and empty cells become `Null`. Other
+    /// unquoted cells are parsed with `Value::parse_typed`, except that
+    /// `null` stays a string. Quoted cells are always strings. Blank lines
+    /// are skipped.
+    pub fn from_csv(text: &str) -> Result<Table, CsvError> {
+        let mut records = parse_records(text)?.into_iter();
+        let header = match records.next() {
+            Some((_, header)) => header,
+            None => return Ok(Table::new()),
+        };
+        let mut columns = vec![];
+        for (text, _) in header {
+            let key = Value::from(text);
+            if columns.contains(&key) {
+                return Err(error(1, "duplicate column"));
+            }
+            columns.push(key);
+        }
+
+        let mut table = Table::new();
+        for (line, record) in records {
+            if record.len() > columns.len() {
+                return Err(error(line, "too many cells"));
+            }
+            let mut cells = record.into_iter();
+            let row: Table = columns.iter().map(|key| {
+                let val = cells.next().map_or(Value::Null, |(text, quoted)| {
+                    if quoted { Value::from(text) } else { infer(text) }
+                });
+                (key.clone(), val)
+            }).collect();
+            table.push(Value::table(row));
+        }
+        Ok(table)
+    }
+}
+
+fn error(line: usize, message: &str) -> CsvError {
+    CsvError { line, message: message.into() }
+}
+
+fn write_record<I: Iterator<Item = String>>(out: &mut String, cells: I) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 { out.push(','); }
+        out.push_str(&cell);
+    }
+    out.push('\n');
+}
+
+/// Escapes a cell, quoting it when needed. Text that does not come from a
+/// string value may be left unquoted even if it reads back as a non-string.
+fn write_text(text: &str, literal: bool) -> String {
+    let special = text.contains([',', '"', '\n', '\r']);
+    if special || (!literal && !infer(text.to_owned()).is_string()) {
+        format!("\"{}\"", text.replace('"', "\"\""))
+    } else {
+        text.to_owned()
+    }
+}
+
+fn infer(text: String) -> Value {
+    match &*text {
+        "" => Value::Null,
+        "null" => Value::from(text),
+        _ => Value::parse_typed(&text),
+    }
+}
+
+/// The line where a record starts, and its `(text, quoted)` cells.
+type Record = (usize, Vec<(String, bool)>);
+
+fn parse_records(text: &str) -> Result<Vec<Record>, CsvError> {
+    let mut records = vec![];
+    let mut record = vec![];
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = text.chars().peekable();
+    loop {
+        match chars.next() {
+            Some('"') if cell.is_empty() && !quoted => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            cell.push('"');
+                        }
+                        Some('"') => break,
+                        Some(ch) => {
+                            if ch == '\n' { line += 1; }
+                            cell.push(ch);
+                        }
+                        None => return Err(error(line, "unterminated quote")),
+                    }
+                }
+            }
+            Some(',') => {
+                record.push((cell, quoted));
+                cell = String::new();
+                quoted = false;
+            }
+            Some('\r') if chars.peek() == Some(&'\n') => {}
+            ch @ Some('\n') | ch @ None => {
+                let blank = record.is_empty() && cell.is_empty() && !quoted;
+                if !blank {
+                    record.push((cell, quoted));
+                    records.push((start, record));
+                }
+                if ch.is_none() {
+                    return Ok(records);
+                }
+                record = vec![];
+                cell = String::new();
+                quoted = false;
+                line += 1;
+                start = line;
+            }
+            Some(_) if quoted => {
+                return Err(error(line, "unexpected character after quote"));
+            }
+            Some(ch) => cell.push(ch),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_csv() {
+        let rows = table!{
+            0 => table!{ "name" => "Sven", "level" => 3, "note" => "a, \"b\"" },
+            1 => table!{ "name" => "12", "alive" => true },
+            2 => table!{ "name" => "", "level" => Value::Null },
+        };
+        assert_eq!(rows.to_csv(), "\
+alive,level,name,note
+,3,Sven,\"a, \"\"b\"\"\"
+true,,\"12\",
+,,\"\",
+");
+        assert_eq!(Table::from_csv(&rows.to_csv()), Ok(table!{
+            0 => table!{ "name" => "Sven", "level" => 3usize, "note" => "a, \"b\"",
+                "alive" => Value::Null },
+            1 => table!{ "name" => "12", "level" => Value::Null, "note" => Value::Null,
+                "alive" => true },
+            2 => table!{ "name" => "", "level" => Value::Null, "note" => Value::Null,
+                "alive" => Value::Null },
+        }));
+        assert_eq!(Table::new().to_csv(), "");
+    }
+
+    #[test]
+    fn test_from_csv() {
+        let text = "a,b,c\r\n1,-2,x y\n\n2.5,\"multi\nline\"\n";
+        assert_eq!(Table::from_csv(text), Ok(table!{
+            0 => table!{ "a" => 1usize, "b" => -2i64, "c" => "x y" },
+            1 => table!{ "a" => 2.5, "b" => "multi\nline", "c" => Value::Null },
+        }));
+        assert_eq!(Table::from_csv(""), Ok(Table::new()));
+        assert_eq!(Table::from_csv("a,a\n").unwrap_err().line, 1);
+        assert_eq!(Table::from_csv("a\n1\n1,2\n"),
+            Err(CsvError { line: 3, message: "too many cells".into() }));
+        assert_eq!(Table::from_csv("a\n\"1\"x\n").unwrap_err().line, 2);
+        assert_eq!(Table::from_csv("a\n\"1\n\n").unwrap_err().message,
+            "unterminated quote");
+        let words = Table::from_csv("a\ninf\nNaN\n").unwrap();
+        assert_eq!(words[0].get("a"), Some(&Value::str("inf")));
+    }
+}
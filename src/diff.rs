@@ -0,0 +1,150 @@
+//! Structural differences between tables.
+
+use { Table, Value };
+
+/// Describes how a single entry differs between two tables.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Change {
+    /// The entry only exists in the new table.
+    Added(Value),
+    /// The entry only exists in the old table.
+    Removed(Value),
+    /// The entry holds a different value in the new table.
+    Changed {
+        /// The value in the old table.
+        old: Value,
+        /// The value in the new table.
+        new: Value,
+    },
+    /// Both tables hold a table under the key, which differ.
+    Nested(TableDiff),
+}
+
+/// The changes that turn one table into another.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct TableDiff {
+    /// The changed entries, sorted by key.
+    pub changes: Vec<(Value, Change)>,
+}
+
+impl TableDiff {
+    /// Returns `true` if the tables were equal.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+impl Table {
+    /// Computes the changes that turn this table into `other`.
+    ///
+    /// Where both tables hold a table under the same key, the change is
+    /// described by a nested diff instead of replacing the whole table.
+    pub fn diff(&self, other: &Table) -> TableDiff {
+        let mut changes = vec![];
+        for (key, old) in self.iter() {
+            match other.get(key) {
+                None => changes.push((key.clone(), Change::Removed(old.clone()))),
+                Some(new) if new == old => {}
+                Some(new) => {
+                    let change = match (old, new) {
+                        (&Value::Table(ref a), &Value::Table(ref b)) => {
+                            Change::Nested(a.diff(b))
+                        }
+                        _ => Change::Changed { old: old.clone(), new: new.clone() },
+                    };
+                    changes.push((key.clone(), change));
+                }
+            }
+        }
+        for (key, new) in other.iter() {
+            if !self.contains_key(key) {
+                changes.push((key.clone(), Change::Added(new.clone())));
+            }
+        }
+        changes.sort_by(|a, b| a.0.cmp(&b.0));
+        TableDiff { changes }
+    }
+
+    /// Replays a diff onto this table.
+    ///
+    /// Applying `a.diff(&b)` to `a` turns it into `b`. When applied to
+    /// another table, nested changes create missing tables as needed and
+    /// removals of missing keys are ignored.
+    pub fn apply_diff(&mut self, diff: &TableDiff) {
+        for &(ref key, ref change) in &diff.changes {
+            match *change {
+                Change::Added(ref new) | Change::Changed { ref new, .. } => {
+                    self.insert(key.clone(), new.clone());
+                }
+                Change::Removed(_) => {
+                    self.remove(key);
+                }
+                Change::Nested(ref diff) => {
+                    self.entry_table(key.clone()).apply_diff(diff);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn old() -> Table {
+        table!{
+            "name" => "Sven",
+            "level" => 3usize,
+            "pos" => table!{ "x" => 1.0, "y" => 2.0 },
+            "tags" => table!{ 0usize => "a" },
+        }
+    }
+
+    fn new() -> Table {
+        table!{
+            "name" => "Sven",
+            "level" => 4usize,
+            "pos" => table!{ "x" => 1.0, "z" => 3.0 },
+            "tags" => "none",
+            "alive" => Value::Bool(true),
+        }
+    }
+
+    #[test]
+    fn test_diff() {
+        let diff = old().diff(&new());
+        assert_eq!(diff.changes, vec![
+            (Value::str("alive"), Change::Added(Value::Bool(true))),
+            (Value::str("level"), Change::Changed {
+                old: Value::Usize(3),
+                new: Value::Usize(4),
+            }),
+            (Value::str("pos"), Change::Nested(TableDiff { changes: vec![
+                (Value::str("y"), Change::Removed(Value::f64(2.0))),
+                (Value::str("z"), Change::Added(Value::f64(3.0))),
+            ]})),
+            (Value::str("tags"), Change::Changed {
+                old: Value::table(table!{ 0usize => "a" }),
+                new: Value::str("none"),
+            }),
+        ]);
+        assert!(old().diff(&old()).is_empty());
+        assert!(Table::new().diff(&Table::new()).is_empty());
+    }
+
+    #[test]
+    fn test_apply_diff() {
+        let mut a = old();
+        a.apply_diff(&old().diff(&new()));
+        assert_eq!(a, new());
+
+        let mut b = new();
+        b.apply_diff(&new().diff(&old()));
+        assert_eq!(b, old());
+
+        let mut c = Table::new();
+        c.apply_diff(&old().diff(&new()));
+        assert_eq!(c["pos"], Value::table(table!{ "z" => 3.0 }));
+        assert_eq!(c.get_str("tags"), Some(&Value::str("none")));
+    }
+}
@@ -0,0 +1,134 @@
+//! Reading tables from environment variables.
+
+use std::env;
+
+use { Table, Value };
+
+impl Table {
+    /// Reads the environment variables whose names start with a prefix.
+    ///
+    /// The prefix is removed and the rest of each name is split on the
+    /// separator into the keys of nested tables, so with the prefix `APP`
+    /// and the separator `__`, `APP__DB__PORT=5432` becomes
+    /// `{ "db": { "port": "5432" } }`. An empty separator keeps the rest
+    /// of the name as a single key.
+    ///
+    /// The prefix is matched ignoring ASCII case, and keys are lowercased.
+    /// With a separator, the prefix must end with it or be followed by it,
+    /// so `APPDATA` is not read for the prefix `APP`.
+    /// Empty segments are skipped, so `APP__DB____PORT` is read like
+    /// `APP__DB__PORT`, and variables with no keys left are ignored, as
+    /// are variables whose name or value is not valid unicode.
+    ///
+    /// Values are strings, unless `infer_types` is set and they are parsed
+    /// with `Value::parse_typed`. When a variable names a table that
+    /// another variable nests into, like `APP__DB` next to `APP__DB__PORT`,
+    /// the nested table wins. Variables that differ only in case are read
+    /// in sorted order of their names, and the last one wins.
+    ///
+    /// ```
+    /// use std::env;
+    /// use table::{ Table, Value };
+    ///
+    /// env::set_var("DOC_APP__DB__PORT", "5432");
+    /// let config = Table::from_env_prefixed("DOC_APP", "__", true);
+    /// assert_eq!(config.get_path("db.port"), Some(&Value::Usize(5432)));
+    /// ```
+    pub fn from_env_prefixed(prefix: &str, separator: &str, infer_types: bool) -> Table {
+        let vars = env::vars_os().filter_map(|(name, val)| {
+            Some((name.into_string().ok()?, val.into_string().ok()?))
+        });
+        from_vars(vars, prefix, separator, infer_types)
+    }
+}
+
+fn from_vars<I>(vars: I, prefix: &str, separator: &str, infer_types: bool) -> Table
+    where I: Iterator<Item = (String, String)>
+{
+    let mut entries: Vec<(Vec<String>, String, String)> = vars.filter_map(|(name, val)| {
+        let rest = match name.get(..prefix.len()) {
+            Some(start) if start.eq_ignore_ascii_case(prefix) => &name[prefix.len()..],
+            _ => return None,
+        };
+        let separated = separator.is_empty() || prefix.ends_with(separator)
+            || rest.is_empty() || rest.starts_with(separator);
+        if !separated {
+            return None;
+        }
+        let keys: Vec<String> = if separator.is_empty() {
+            vec![rest.to_lowercase()]
+        } else {
+            rest.split(separator).map(str::to_lowercase).collect()
+        };
+        let keys: Vec<String> = keys.into_iter().filter(|key| !key.is_empty()).collect();
+        if keys.is_empty() {
+            return None;
+        }
+        Some((keys, name, val))
+    }).collect();
+    // Sorting puts each variable before those nesting into it.
+    entries.sort();
+
+    let mut table = Table::new();
+    for (keys, _, val) in entries {
+        let (last, parents) = keys.split_last().unwrap();
+        let parent = parents.iter().fold(&mut table, |table, key| {
+            table.entry_table(&**key)
+        });
+        let val = if infer_types { Value::parse_typed(&val) } else { Value::from(val) };
+        parent.insert(Value::str(last), val);
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+
+    use super::from_vars;
+    use { Table, Value };
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|&(name, val)| (name.to_owned(), val.to_owned())).collect()
+    }
+
+    #[test]
+    fn test_from_vars() {
+        let input = vars(&[
+            ("APP__DB__PORT", "5432"),
+            ("APP__DB__HOST", "localhost"),
+            ("app__Debug", "true"),
+            ("APP____NAME__", "demo"),
+            ("APP__", "ignored"),
+            ("AP", "x"),
+            ("APPDATA", "C:\\x"),
+            ("APPLE__X", "1"),
+            ("OTHER__DB__PORT", "1"),
+        ]);
+        let table = from_vars(input.clone().into_iter(), "APP", "__", false);
+        assert_eq!(table, table!{
+            "db" => table!{ "port" => "5432", "host" => "localhost" },
+            "debug" => "true",
+            "name" => "demo",
+        });
+        let table = from_vars(input.into_iter(), "APP", "__", true);
+        assert_eq!(table.get_path("db.port"), Some(&Value::Usize(5432)));
+        assert_eq!(table["debug"], Value::Bool(true));
+
+        let input = vars(&[("APP_DB_PORT", "1"), ("APP_DB", "2"), ("APP_db", "3")]);
+        let table = from_vars(input.into_iter(), "APP_", "_", false);
+        assert_eq!(table, table!{ "db" => table!{ "port" => "1" } });
+
+        let input = vars(&[("APP_A_B", "1"), ("APP_a_b", "2")]);
+        let table = from_vars(input.into_iter(), "APP_", "", false);
+        assert_eq!(table, table!{ "a_b" => "2" });
+    }
+
+    #[test]
+    fn test_from_env_prefixed() {
+        env::set_var("TABLE_TEST_ENV__ONE", "1");
+        env::set_var("TABLE_TEST_ENV__NESTED__TWO", "two");
+        let table = Table::from_env_prefixed("TABLE_TEST_ENV", "__", false);
+        assert_eq!(table, table!{ "one" => "1", "nested" => table!{ "two" => "two" } });
+    }
+}
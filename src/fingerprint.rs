@@ -0,0 +1,156 @@
+//! Deterministic fingerprints of values.
+
+use { Number, Value, F64 };
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+impl Value {
+    /// Returns a 64 bit hash that is the same in every run and on every
+    /// machine.
+    ///
+    /// The `Hash` implementation gives different results from run to run,
+    /// since `HashMap` seeds its hasher randomly. The fingerprint does not
+    /// depend on a seed, on the iteration order of tables or on the
+    /// platform, so it can be stored, for example as a cache key on disk.
+    /// Values that are equal have the same fingerprint, so `Usize(1)` and
+    /// `F64(1.0)` do. Different values rarely collide, but this is not a
+    /// cryptographic hash, so do not rely on it against crafted input.
+    ///
+    /// The algorithm is part of the API and will not change. The result
+    /// is the 64 bit FNV-1a hash of an encoding of the value, where
+    /// integers are written little-endian and lengths as `u64`:
+    ///
+    /// - `Null` is the byte 0, and a bool is 1 followed by 0 or 1.
+    /// - A number that is whole and within `i64::MIN..=u64::MAX` is 2
+    ///   followed by the `i128` value. Other numbers are 3 followed by the
+    ///   `f64` bits, with every NaN written as `0x7ff8000000000000`.
+    /// - A char is 4 followed by its code point as `u32`.
+    /// - A string is 5, and bytes are 6, followed by the length and the
+    ///   UTF-8 or raw bytes.
+    /// - An array is 7 followed by the length and the elements.
+    /// - A table is 8 followed by the number of entries and the wrapping
+    ///   sum, as `u64`, of a hash per entry. That hash is the FNV-1a hash
+    ///   of the encoded key followed by the encoded value, so the order of
+    ///   entries does not matter.
+    pub fn fingerprint(&self) -> u64 {
+        let mut fnv = Fnv(FNV_OFFSET);
+        fnv.value(self);
+        fnv.0
+    }
+}
+
+struct Fnv(u64);
+
+impl Fnv {
+    fn bytes(&mut self, bytes: &[u8]) {
+        for &b in bytes {
+            self.0 = (self.0 ^ b as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn len(&mut self, len: usize) {
+        self.bytes(&(len as u64).to_le_bytes());
+    }
+
+    fn value(&mut self, val: &Value) {
+        match *val {
+            Value::Null => self.bytes(&[0]),
+            Value::Bool(x) => self.bytes(&[1, x as u8]),
+            Value::Usize(_) | Value::U64(_) | Value::I64(_) | Value::F64(_) => {
+                match Number::of(val).unwrap() {
+                    Number::Int(x) => self.int(x),
+                    Number::Float(x) => {
+                        let range = i64::MIN as f64..=u64::MAX as f64;
+                        if x.fract() == 0.0 && range.contains(&x) {
+                            self.int(x as i128)
+                        } else {
+                            self.bytes(&[3]);
+                            self.bytes(&F64(x).canonical_bits().to_le_bytes());
+                        }
+                    }
+                }
+            }
+            Value::Char(ch) => {
+                self.bytes(&[4]);
+                self.bytes(&(ch as u32).to_le_bytes());
+            }
+            Value::String(ref text) => {
+                self.bytes(&[5]);
+                self.len(text.len());
+                self.bytes(text.as_bytes());
+            }
+            Value::Bytes(ref bytes) => {
+                self.bytes(&[6]);
+                self.len(bytes.len());
+                self.bytes(bytes);
+            }
+            Value::Array(ref items) => {
+                self.bytes(&[7]);
+                self.len(items.len());
+                for item in items.iter() {
+                    self.value(item);
+                }
+            }
+            Value::Table(ref table) => {
+                self.bytes(&[8]);
+                self.len(table.len());
+                let sum = table.iter().fold(0u64, |sum, (key, val)| {
+                    let mut entry = Fnv(FNV_OFFSET);
+                    entry.value(key);
+                    entry.value(val);
+                    sum.wrapping_add(entry.0)
+                });
+                self.bytes(&sum.to_le_bytes());
+            }
+        }
+    }
+
+    fn int(&mut self, x: i128) {
+        self.bytes(&[2]);
+        self.bytes(&x.to_le_bytes());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use { Table, Value };
+
+    #[test]
+    fn test_fingerprint_equal_values() {
+        assert_eq!(Value::Usize(1).fingerprint(), Value::f64(1.0).fingerprint());
+        assert_eq!(Value::U64(7).fingerprint(), Value::I64(7).fingerprint());
+        assert_eq!(Value::f64(-0.0).fingerprint(), Value::f64(0.0).fingerprint());
+        let nan = Value::f64(f64::NAN).fingerprint();
+        assert_eq!(nan, Value::f64(-f64::NAN).fingerprint());
+        assert!(Value::f64(0.5).fingerprint() != Value::f64(1.5).fingerprint());
+        assert!(Value::str("a").fingerprint() != Value::Char('a').fingerprint());
+        assert!(Value::str("a").fingerprint() != Value::from(&b"a"[..]).fingerprint());
+        let pair = Value::array(vec![Value::str("ab"), Value::str("c")]);
+        let split = Value::array(vec![Value::str("a"), Value::str("bc")]);
+        assert!(pair.fingerprint() != split.fingerprint());
+
+        let forward: Table = (0..100usize).map(|i| (i, i * i)).collect();
+        let backward: Table = (0..100usize).rev().map(|i| (i, i * i)).collect();
+        assert_eq!(Value::table(forward.clone()).fingerprint(),
+            Value::table(backward).fingerprint());
+        let swapped: Table = (0..100usize).map(|i| (i * i, i)).collect();
+        let forward = Value::table(forward).fingerprint();
+        assert!(forward != Value::table(swapped).fingerprint());
+    }
+
+    #[test]
+    fn test_fingerprint_frozen() {
+        // These values must never change, since fingerprints may be stored.
+        assert_eq!(Value::Null.fingerprint(), 0xaf63_bd4c_8601_b7df);
+        assert_eq!(Value::Usize(1).fingerprint(), 0x9caa_bf28_9892_ec44);
+        assert_eq!(Value::str("table").fingerprint(), 0x3b0c_c72f_e411_f29f);
+        let val = Value::table(table!{
+            "name" => "Sven",
+            "pos" => vec![Value::f64(1.5), Value::I64(-2)],
+            "tag" => 'x',
+            "ok" => true,
+        });
+        assert_eq!(val.fingerprint(), 0x13ba_84a8_9033_6b25);
+    }
+}
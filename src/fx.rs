@@ -0,0 +1,186 @@
+//! A fast hasher for tables of trusted data, enabled by the `fxhash`
+//! feature.
+
+use std::collections::HashMap;
+use std::hash::{ BuildHasherDefault, Hasher };
+
+use { Table, Value };
+
+const SEED: u64 = 0x51_7c_c1_b7_27_22_0a_95;
+
+/// The hash function used by the Rust compiler for its own tables.
+///
+/// It mixes each word of input with a rotation, an xor and a
+/// multiplication, which is much faster than the SipHash that `HashMap`
+/// uses by default. It has no random seed, so input chosen to
+/// collide can make a map very slow. Use it only for data that does not
+/// come from an untrusted source.
+#[derive(Copy, Clone, Default, Debug)]
+pub struct FxHasher {
+    hash: u64,
+}
+
+impl FxHasher {
+    fn add(&mut self, word: u64) {
+        self.hash = (self.hash.rotate_left(5) ^ word).wrapping_mul(SEED);
+    }
+}
+
+impl Hasher for FxHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        let mut chunks = bytes.chunks_exact(8);
+        for chunk in &mut chunks {
+            let mut word = [0; 8];
+            word.copy_from_slice(chunk);
+            self.add(u64::from_le_bytes(word));
+        }
+        for &b in chunks.remainder() {
+            self.add(b as u64);
+        }
+    }
+
+    fn write_u8(&mut self, x: u8) {
+        self.add(x as u64);
+    }
+
+    fn write_u32(&mut self, x: u32) {
+        self.add(x as u64);
+    }
+
+    fn write_u64(&mut self, x: u64) {
+        self.add(x);
+    }
+
+    fn write_u128(&mut self, x: u128) {
+        self.add(x as u64);
+        self.add((x >> 64) as u64);
+    }
+
+    fn write_usize(&mut self, x: usize) {
+        self.add(x as u64);
+    }
+
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+}
+
+/// Creates `FxHasher`s for a `HashMap`.
+pub type FxBuildHasher = BuildHasherDefault<FxHasher>;
+
+/// A map with the same keys and values as `Table`, hashed with `FxHasher`.
+///
+/// Create one with `FxTable::default()` or `Table::into_fx`, and convert
+/// it back with `Table::from`. Only the hasher differs, so lookups find
+/// the same entries as in a `Table`.
+pub type FxTable = HashMap<Value, Value, FxBuildHasher>;
+
+impl Table {
+    /// Moves the entries into a map hashed with `FxHasher`.
+    pub fn into_fx(self) -> FxTable {
+        self.0.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use self::test::Bencher;
+    use std::collections::hash_map::RandomState;
+    use std::hash::{ BuildHasher, Hash };
+
+    fn fx_hash<T: Hash>(val: T) -> u64 {
+        let mut hasher = FxHasher::default();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_fx_table() {
+        let a = table!{ "x" => 1.0, 2usize => "two", Value::Null => true };
+        let mut fx = a.clone().into_fx();
+        assert_eq!(fx.len(), 3);
+        assert_eq!(fx[&Value::str("x")], Value::f64(1.0));
+        assert_eq!(fx[&Value::U64(2)], Value::str("two"));
+        fx.insert(Value::f64(2.0), Value::str("deux"));
+        assert_eq!(fx.len(), 3);
+        let back = Table::from(fx);
+        assert_eq!(back[2], Value::str("deux"));
+
+        assert_eq!(fx_hash(Value::Usize(5)), fx_hash(Value::f64(5.0)));
+        assert_eq!(fx_hash("abc"), fx_hash(String::from("abc")));
+        assert!(fx_hash(1u64) != fx_hash(2u64));
+        assert!(fx_hash(&b"abcdefghi"[..]) != fx_hash(&b"abcdefghj"[..]));
+    }
+
+    fn int_keys() -> Vec<Value> {
+        (0..1000).map(Value::Usize).collect()
+    }
+
+    fn str_keys() -> Vec<Value> {
+        (0..1000).map(|i| Value::from(format!("key{}", i))).collect()
+    }
+
+    fn bench_insert<S>(bencher: &mut Bencher, keys: &[Value])
+        where S: BuildHasher + Default
+    {
+        bencher.iter(|| {
+            let mut map: HashMap<Value, Value, S> = HashMap::default();
+            for key in keys {
+                map.insert(key.clone(), Value::Null);
+            }
+            map
+        });
+    }
+
+    fn bench_lookup<S>(bencher: &mut Bencher, keys: &[Value])
+        where S: BuildHasher + Default
+    {
+        let map: HashMap<Value, Value, S> = keys.iter()
+            .map(|key| (key.clone(), Value::Null))
+            .collect();
+        bencher.iter(|| keys.iter().filter(|&key| map.contains_key(key)).count());
+    }
+
+    #[bench]
+    fn bench_insert_int_keys_default(bencher: &mut Bencher) {
+        bench_insert::<RandomState>(bencher, &int_keys());
+    }
+
+    #[bench]
+    fn bench_insert_int_keys_fx(bencher: &mut Bencher) {
+        bench_insert::<FxBuildHasher>(bencher, &int_keys());
+    }
+
+    #[bench]
+    fn bench_insert_str_keys_default(bencher: &mut Bencher) {
+        bench_insert::<RandomState>(bencher, &str_keys());
+    }
+
+    #[bench]
+    fn bench_insert_str_keys_fx(bencher: &mut Bencher) {
+        bench_insert::<FxBuildHasher>(bencher, &str_keys());
+    }
+
+    #[bench]
+    fn bench_lookup_int_keys_default(bencher: &mut Bencher) {
+        bench_lookup::<RandomState>(bencher, &int_keys());
+    }
+
+    #[bench]
+    fn bench_lookup_int_keys_fx(bencher: &mut Bencher) {
+        bench_lookup::<FxBuildHasher>(bencher, &int_keys());
+    }
+
+    #[bench]
+    fn bench_lookup_str_keys_default(bencher: &mut Bencher) {
+        bench_lookup::<RandomState>(bencher, &str_keys());
+    }
+
+    #[bench]
+    fn bench_lookup_str_keys_fx(bencher: &mut Bencher) {
+        bench_lookup::<FxBuildHasher>(bencher, &str_keys());
+    }
+}
@@ -0,0 +1,118 @@
+//! Tables with a cached hash.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{ Hash, Hasher };
+use std::ops::Deref;
+use std::sync::Arc;
+
+use Table;
+
+/// A shared table with its hash computed once.
+///
+/// Hashing a `Table` visits every entry, which is slow when a large table
+/// is used as a key for repeated lookups. `HashedTable` computes the hash
+/// when it is created and feeds only that to hashers afterwards. The table
+/// can not be changed while wrapped.
+#[derive(Clone, Debug)]
+pub struct HashedTable {
+    table: Arc<Table>,
+    hash: u64,
+}
+
+impl HashedTable {
+    /// Wraps a table, computing its hash.
+    pub fn new(table: Table) -> HashedTable {
+        HashedTable::from_arc(Arc::new(table))
+    }
+
+    /// Wraps a shared table, computing its hash.
+    pub fn from_arc(table: Arc<Table>) -> HashedTable {
+        let mut hasher = DefaultHasher::new();
+        table.hash(&mut hasher);
+        HashedTable { hash: hasher.finish(), table }
+    }
+
+    /// Returns the cached hash.
+    pub fn hash_code(&self) -> u64 {
+        self.hash
+    }
+
+    /// Returns the shared table.
+    pub fn into_inner(self) -> Arc<Table> {
+        self.table
+    }
+}
+
+impl Deref for HashedTable {
+    type Target = Table;
+
+    fn deref(&self) -> &Table {
+        &self.table
+    }
+}
+
+impl PartialEq for HashedTable {
+    fn eq(&self, other: &HashedTable) -> bool {
+        self.hash == other.hash &&
+        (Arc::ptr_eq(&self.table, &other.table) || self.table == other.table)
+    }
+}
+
+impl Eq for HashedTable {}
+
+impl Hash for HashedTable {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        state.write_u64(self.hash)
+    }
+}
+
+impl From<Table> for HashedTable {
+    fn from(table: Table) -> HashedTable {
+        HashedTable::new(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use self::test::Bencher;
+    use std::collections::HashMap;
+    use Value;
+
+    fn large() -> Table {
+        (0..1000).map(|i| (i, Value::str("item"))).collect()
+    }
+
+    #[test]
+    fn test_hashed_table() {
+        let a = HashedTable::new(large());
+        let b = a.clone();
+        assert_eq!(a, b);
+        assert_eq!(a.hash_code(), b.hash_code());
+        assert_eq!(a.len(), 1000);
+        assert!(a != HashedTable::new(Table::new()));
+
+        let mut map = HashMap::new();
+        map.insert(a.clone(), 1);
+        assert_eq!(map.get(&b), Some(&1));
+        assert_eq!(*a.into_inner(), *b);
+    }
+
+    #[bench]
+    fn bench_lookup_table_key(bencher: &mut Bencher) {
+        let key = large();
+        let mut map = HashMap::new();
+        map.insert(key.clone(), 1);
+        bencher.iter(|| map.get(&key).cloned());
+    }
+
+    #[bench]
+    fn bench_lookup_hashed_table_key(bencher: &mut Bencher) {
+        let key = HashedTable::new(large());
+        let mut map = HashMap::new();
+        map.insert(key.clone(), 1);
+        bencher.iter(|| map.get(&key).cloned());
+    }
+}
@@ -0,0 +1,708 @@
+//! Reading and writing values as JSON.
+
+use std::error::Error;
+use std::fmt::{ self, Write };
+use std::io::{ self, BufRead };
+
+use codec::encode_base64;
+use { OrderedTable, Table, Value };
+
+/// The deepest nesting of arrays and objects that the parsers accept.
+///
+/// Parsing recurses once per level, so without a limit deeply nested
+/// input could overflow the stack.
+pub const MAX_DEPTH: usize = 128;
+
+/// An error returned when parsing JSON fails.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ParseError {
+    /// The byte offset where the error was detected.
+    pub offset: usize,
+    /// A description of what went wrong.
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl Error for ParseError {}
+
+impl Value {
+    /// Writes the value as compact JSON.
+    ///
+    /// Arrays and tables with keys `0..n` are written as arrays, other
+    /// tables as objects with entries sorted by key. Non-string keys are
+    /// written as strings holding their JSON text. NaN and infinite floats
+    /// have no JSON representation and are written as `null`. Chars are
+    /// written as strings and bytes as base64 strings, which both read back
+    /// as strings.
+    pub fn to_json_string(&self) -> String {
+        let mut out = String::new();
+        write_json(&mut out, self, None, 0).unwrap();
+        out
+    }
+
+    /// Writes the value as JSON indented by two spaces per level.
+    pub fn to_json_pretty(&self) -> String {
+        let mut out = String::new();
+        write_json(&mut out, self, Some("  "), 0).unwrap();
+        out
+    }
+
+    /// Writes the value as compact JSON directly to a writer.
+    ///
+    /// The output is the same as `to_json_string`, but it is pushed to the
+    /// writer piece by piece instead of being built up in memory first.
+    /// Many small writes are made, so wrap unbuffered writers like files
+    /// and sockets in a `BufWriter`.
+    pub fn to_writer<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        write_io(w, self, None)
+    }
+
+    /// Works like `to_writer`, but indents like `to_json_pretty`.
+    pub fn to_writer_pretty<W: io::Write>(&self, w: &mut W) -> io::Result<()> {
+        write_io(w, self, Some("  "))
+    }
+
+    /// Parses a value from JSON.
+    ///
+    /// Objects become tables keyed by strings and arrays become `Array`
+    /// values. Integers become `Usize` when non-negative and `I64` when
+    /// negative, falling back to `U64` and then `F64` when out of range.
+    /// Other numbers become `F64`.
+    ///
+    /// Arrays and objects may be nested at most 128 levels deep, deeper
+    /// input gives an error with the message `nesting too deep`.
+    pub fn from_json_str(text: &str) -> Result<Value, ParseError> {
+        Parser::new(text.as_bytes()).document()
+    }
+
+    /// Reads JSON from a reader and parses it like `from_json_str`.
+    ///
+    /// The input is parsed as it is read through a small buffer, so the
+    /// text is never held in memory as a whole. After the value, the rest
+    /// of the input is read to check that only whitespace follows. Invalid
+    /// JSON, including invalid UTF-8 and input nested too deeply, is
+    /// reported as an `io::ErrorKind::InvalidData` error wrapping a
+    /// `ParseError`. Errors of the reader are returned as they are.
+    pub fn from_reader<R: io::Read>(r: &mut R) -> io::Result<Value> {
+        let input = ReadInput { reader: io::BufReader::new(r), error: None };
+        let mut parser = Parser::new(input);
+        let val = parser.document();
+        if let Some(err) = parser.input.error.take() {
+            return Err(err);
+        }
+        val.map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+impl OrderedTable {
+    /// Writes the table as a compact JSON object, with the entries in
+    /// insertion order.
+    ///
+    /// Values are written like in `Value::to_json_string`, so nested tables
+    /// have their entries sorted by key. Unlike a `Table`, an ordered table
+    /// is written as an object even if its keys are `0..n`.
+    pub fn to_json_string(&self) -> String {
+        let mut out = String::new();
+        write_object(&mut out, self.iter().collect(), None, 0).unwrap();
+        out
+    }
+
+    /// Works like `to_json_string`, but indents like `Value::to_json_pretty`.
+    pub fn to_json_pretty(&self) -> String {
+        let mut out = String::new();
+        write_object(&mut out, self.iter().collect(), Some("  "), 0).unwrap();
+        out
+    }
+}
+
+/// Adapts an `io::Write` to `fmt::Write`, keeping the first I/O error.
+struct IoWriter<'a, W: 'a> {
+    inner: &'a mut W,
+    error: Option<io::Error>,
+}
+
+impl<'a, W: io::Write> Write for IoWriter<'a, W> {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        self.inner.write_all(text.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+fn write_io<W: io::Write>(
+    w: &mut W,
+    val: &Value,
+    indent: Option<&str>
+) -> io::Result<()> {
+    let mut w = IoWriter { inner: w, error: None };
+    write_json(&mut w, val, indent, 0)
+        .map_err(|_| w.error.unwrap_or_else(|| io::Error::other("formatting failed")))
+}
+
+/// Returns the length if the table has exactly the keys `0..len`.
+fn array_len(table: &Table) -> Option<usize> {
+    if !table.is_empty() && table.is_array() {
+        Some(table.len())
+    } else {
+        None
+    }
+}
+
+fn write_json<W: Write>(
+    w: &mut W,
+    val: &Value,
+    indent: Option<&str>,
+    depth: usize
+) -> fmt::Result {
+    match *val {
+        Value::Null => w.write_str("null"),
+        Value::Bool(x) => write!(w, "{}", x),
+        Value::Usize(x) => write!(w, "{}", x),
+        Value::U64(x) => write!(w, "{}", x),
+        Value::I64(x) => write!(w, "{}", x),
+        Value::F64(x) => {
+            if x.is_finite() { write!(w, "{:?}", x.0) } else { w.write_str("null") }
+        }
+        Value::Char(ch) => write_json_str(w, ch.encode_utf8(&mut [0; 4])),
+        Value::String(ref text) => write_json_str(w, text),
+        Value::Bytes(ref bytes) => write_json_str(w, &encode_base64(bytes)),
+        Value::Array(ref items) => {
+            w.write_char('[')?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 { w.write_char(',')?; }
+                write_newline(w, indent, depth + 1)?;
+                write_json(w, item, indent, depth + 1)?;
+            }
+            if !items.is_empty() {
+                write_newline(w, indent, depth)?;
+            }
+            w.write_char(']')
+        }
+        Value::Table(ref table) => {
+            if let Some(len) = array_len(table) {
+                w.write_char('[')?;
+                for i in 0..len {
+                    if i > 0 { w.write_char(',')?; }
+                    write_newline(w, indent, depth + 1)?;
+                    write_json(w, &table[i], indent, depth + 1)?;
+                }
+                write_newline(w, indent, depth)?;
+                return w.write_char(']');
+            }
+
+            write_object(w, table.sorted_entries(), indent, depth)
+        }
+    }
+}
+
+/// Writes entries as a JSON object in the given order.
+fn write_object<W: Write>(
+    w: &mut W,
+    entries: Vec<(&Value, &Value)>,
+    indent: Option<&str>,
+    depth: usize
+) -> fmt::Result {
+    w.write_char('{')?;
+    let empty = entries.is_empty();
+    for (i, (key, val)) in entries.into_iter().enumerate() {
+        if i > 0 { w.write_char(',')?; }
+        write_newline(w, indent, depth + 1)?;
+        match *key {
+            Value::String(ref text) => write_json_str(w, text)?,
+            _ => write_json_str(w, &key.to_json_string())?,
+        }
+        w.write_str(if indent.is_some() { ": " } else { ":" })?;
+        write_json(w, val, indent, depth + 1)?;
+    }
+    if !empty {
+        write_newline(w, indent, depth)?;
+    }
+    w.write_char('}')
+}
+
+fn write_newline<W: Write>(
+    w: &mut W,
+    indent: Option<&str>,
+    depth: usize
+) -> fmt::Result {
+    if let Some(indent) = indent {
+        w.write_char('\n')?;
+        for _ in 0..depth {
+            w.write_str(indent)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_json_str<W: Write>(w: &mut W, text: &str) -> fmt::Result {
+    w.write_char('"')?;
+    for ch in text.chars() {
+        match ch {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            '\u{8}' => w.write_str("\\b")?,
+            '\u{c}' => w.write_str("\\f")?,
+            ch if (ch as u32) < 0x20 => write!(w, "\\u{:04x}", ch as u32)?,
+            ch => w.write_char(ch)?,
+        }
+    }
+    w.write_char('"')
+}
+
+/// The bytes that a `Parser` reads, one at a time.
+trait Input {
+    /// Returns the next byte without consuming it, or `None` at the end.
+    fn peek(&mut self) -> Option<u8>;
+
+    /// Consumes the byte returned by `peek`.
+    fn advance(&mut self);
+}
+
+impl<'a> Input for &'a [u8] {
+    fn peek(&mut self) -> Option<u8> {
+        self.first().cloned()
+    }
+
+    fn advance(&mut self) {
+        *self = &self[1..];
+    }
+}
+
+/// Reads input through a buffer, keeping the first I/O error.
+///
+/// After an error the input ends, so the parser stops, and the caller
+/// reports the error instead of the parse result.
+struct ReadInput<R> {
+    reader: io::BufReader<R>,
+    error: Option<io::Error>,
+}
+
+impl<R: io::Read> Input for ReadInput<R> {
+    fn peek(&mut self) -> Option<u8> {
+        if self.error.is_some() {
+            return None;
+        }
+        loop {
+            match self.reader.fill_buf() {
+                Ok(buf) => return buf.first().cloned(),
+                Err(ref err) if err.kind() == io::ErrorKind::Interrupted => {}
+                Err(err) => {
+                    self.error = Some(err);
+                    return None;
+                }
+            }
+        }
+    }
+
+    fn advance(&mut self) {
+        self.reader.consume(1);
+    }
+}
+
+struct Parser<I> {
+    input: I,
+    pos: usize,
+    /// The number of arrays and objects being parsed.
+    depth: usize,
+}
+
+impl<I: Input> Parser<I> {
+    fn new(input: I) -> Parser<I> {
+        Parser { input, pos: 0, depth: 0 }
+    }
+
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { offset: self.pos, message: message.into() }
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.input.peek()
+    }
+
+    fn bump(&mut self) {
+        self.input.advance();
+        self.pos += 1;
+    }
+
+    /// Parses a single value followed by nothing but whitespace.
+    fn document(&mut self) -> Result<Value, ParseError> {
+        let val = self.value()?;
+        self.skip_whitespace();
+        if self.peek().is_some() {
+            return Err(self.error("trailing characters"));
+        }
+        Ok(val)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), ParseError> {
+        for &b in literal.as_bytes() {
+            if self.peek() != Some(b) {
+                return Err(self.error("invalid literal"));
+            }
+            self.bump();
+        }
+        Ok(())
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some(b'n') => self.expect("null").map(|_| Value::Null),
+            Some(b't') => self.expect("true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Value::Bool(false)),
+            Some(b'"') => self.string().map(Value::from),
+            Some(b'[') => self.nested(Parser::array),
+            Some(b'{') => self.nested(Parser::object),
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    /// Parses an array or object one level deeper, see `MAX_DEPTH`.
+    fn nested<F>(&mut self, parse: F) -> Result<Value, ParseError>
+        where F: FnOnce(&mut Parser<I>) -> Result<Value, ParseError>
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let val = parse(self);
+        self.depth -= 1;
+        val
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut items = vec![];
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.bump();
+            return Ok(Value::array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.bump(),
+                Some(b']') => {
+                    self.bump();
+                    return Ok(Value::array(items));
+                }
+                _ => return Err(self.error("expected `,` or `]`")),
+            }
+        }
+    }
+
+    fn object(&mut self) -> Result<Value, ParseError> {
+        self.bump();
+        let mut table = Table::new();
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.bump();
+            return Ok(Value::table(table));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("expected string key"));
+            }
+            let key = self.string()?;
+            self.skip_whitespace();
+            if self.peek() != Some(b':') {
+                return Err(self.error("expected `:`"));
+            }
+            self.bump();
+            let val = self.value()?;
+            table.insert(Value::from(key), val);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(b',') => self.bump(),
+                Some(b'}') => {
+                    self.bump();
+                    return Ok(Value::table(table));
+                }
+                _ => return Err(self.error("expected `,` or `}`")),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, ParseError> {
+        let start = self.pos;
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.peek().and_then(|b| (b as char).to_digit(16));
+            match digit {
+                Some(digit) => code = code * 16 + digit,
+                None => {
+                    let mut err = self.error("invalid unicode escape");
+                    err.offset = start;
+                    return Err(err);
+                }
+            }
+            self.bump();
+        }
+        Ok(code)
+    }
+
+    fn unicode_escape(&mut self) -> Result<char, ParseError> {
+        let mut code = self.hex4()?;
+        if (0xd800..0xdc00).contains(&code) {
+            self.expect("\\u")?;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(self.error("invalid surrogate pair"));
+            }
+            code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+        }
+        ::std::char::from_u32(code).ok_or_else(|| self.error("invalid unicode escape"))
+    }
+
+    fn string(&mut self) -> Result<String, ParseError> {
+        let start = self.pos;
+        self.bump();
+        let mut out = vec![];
+        loop {
+            match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(b'"') => {
+                    self.bump();
+                    // Only a reader can give invalid UTF-8, a `&str` can not.
+                    return String::from_utf8(out).map_err(|_| {
+                        let mut err = self.error("invalid UTF-8 in string");
+                        err.offset = start;
+                        err
+                    });
+                }
+                Some(b'\\') => {
+                    self.bump();
+                    let ch = match self.peek() {
+                        Some(b'"') => '"',
+                        Some(b'\\') => '\\',
+                        Some(b'/') => '/',
+                        Some(b'b') => '\u{8}',
+                        Some(b'f') => '\u{c}',
+                        Some(b'n') => '\n',
+                        Some(b'r') => '\r',
+                        Some(b't') => '\t',
+                        Some(b'u') => {
+                            self.bump();
+                            let ch = self.unicode_escape()?;
+                            out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                            continue;
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    };
+                    self.bump();
+                    out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+                }
+                Some(b) if b < 0x20 => {
+                    return Err(self.error("control character in string"));
+                }
+                Some(b) => {
+                    self.bump();
+                    out.push(b);
+                }
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let mut text = String::new();
+        let mut float = false;
+        if self.peek() == Some(b'-') { self.push(&mut text); }
+        match self.peek() {
+            Some(b'0') => self.push(&mut text),
+            Some(b'1'..=b'9') => { self.digits(&mut text); }
+            _ => return Err(self.error("expected digit")),
+        }
+        if self.peek() == Some(b'.') {
+            float = true;
+            self.push(&mut text);
+            if !self.digits(&mut text) { return Err(self.error("expected digit")); }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            float = true;
+            self.push(&mut text);
+            if let Some(b'+') | Some(b'-') = self.peek() { self.push(&mut text); }
+            if !self.digits(&mut text) { return Err(self.error("expected digit")); }
+        }
+
+        if !float {
+            if let Ok(x) = text.parse::<usize>() { return Ok(Value::Usize(x)); }
+            if let Ok(x) = text.parse::<u64>() { return Ok(Value::U64(x)); }
+            if let Ok(x) = text.parse::<i64>() { return Ok(Value::I64(x)); }
+        }
+        Ok(Value::f64(text.parse().unwrap()))
+    }
+
+    /// Consumes the next byte, which is ASCII, and pushes it to a text.
+    fn push(&mut self, text: &mut String) {
+        text.push(self.peek().unwrap() as char);
+        self.bump();
+    }
+
+    /// Consumes ASCII digits into a text, returning whether there were any.
+    fn digits(&mut self, text: &mut String) -> bool {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.push(text);
+        }
+        self.pos > start
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use { Table, Value };
+
+    #[test]
+    fn test_write() {
+        let mut a = Table::new();
+        a["name"] = Value::str("Sv\"en\n");
+        a["level"] = Value::Usize(3);
+        a["pos"] = Value::table(table!{ 0usize => 1.5, 1usize => -2.0 });
+        a["none"] = Value::Null;
+        a["flag"] = Value::Bool(false);
+        a[Value::I64(-1)] = Value::table(Table::new());
+        let a = Value::table(a);
+        assert_eq!(a.to_json_string(), "{\"-1\":{},\"flag\":false,\"level\":3,\
+            \"name\":\"Sv\\\"en\\n\",\"none\":null,\"pos\":[1.5,-2.0]}");
+        assert_eq!(Value::table(table!{ "a" => table!{ 0usize => 1usize } })
+            .to_json_pretty(), "{\n  \"a\": [\n    1\n  ]\n}");
+        assert_eq!(Value::f64(f64::NAN).to_json_string(), "null");
+    }
+
+    #[test]
+    fn test_parse() {
+        let a = Value::from_json_str(
+            r#" { "a": [1, -2, 18446744073709551616, 2.5e1, "\u00e9\ud83d\ude00"],
+                  "b": {"c": null, "d": true} } "#).unwrap();
+        let a = a.as_table().unwrap();
+        let arr = a["a"].as_array().unwrap();
+        assert_eq!(arr[0], Value::Usize(1));
+        assert_eq!(arr[1].kind(), ::ValueKind::I64);
+        assert_eq!(arr[2].kind(), ::ValueKind::F64);
+        assert_eq!(arr[3], Value::f64(25.0));
+        assert_eq!(arr[4], Value::str("\u{e9}\u{1f600}"));
+        assert_eq!(a["b"].as_table().unwrap()["d"], Value::Bool(true));
+        assert_eq!(a["b"].as_table().unwrap()["c"], Value::Null);
+        assert_eq!(Value::from_json_str("[]").unwrap(), Value::array(vec![]));
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let text = "{\"list\":[{},[true],\"x\\ty\"],\"n\":-0.25}";
+        assert_eq!(Value::from_json_str(text).unwrap().to_json_string(), text);
+        let pretty = Value::from_json_str(text).unwrap().to_json_pretty();
+        assert_eq!(Value::from_json_str(&pretty).unwrap().to_json_string(), text);
+    }
+
+    #[test]
+    fn test_writer_and_reader() {
+        let a = Value::table(table!{
+            "list" => vec![Value::str("x\ty"), Value::f64(-0.25)],
+            "n" => Value::Null,
+        });
+        let mut out = vec![];
+        a.to_writer(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), a.to_json_string());
+        let mut out = vec![];
+        a.to_writer_pretty(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), a.to_json_pretty());
+
+        let text = a.to_json_string();
+        assert_eq!(Value::from_reader(&mut text.as_bytes()).unwrap(), a);
+        let err = Value::from_reader(&mut &b"[1,"[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "unexpected end of input at byte 3");
+        let err = Value::from_reader(&mut &b"\"\xff\""[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        let deep = "[".repeat(100_000);
+        let err = Value::from_reader(&mut deep.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(err.to_string(), "nesting too deep at byte 128");
+    }
+
+    /// Reads one byte per call, then fails if `fail` is set.
+    struct Trickle<'a> {
+        data: &'a [u8],
+        fail: bool,
+    }
+
+    impl<'a> io::Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.data.split_first() {
+                Some((&b, rest)) if !buf.is_empty() => {
+                    buf[0] = b;
+                    self.data = rest;
+                    Ok(1)
+                }
+                _ if self.fail => Err(io::ErrorKind::BrokenPipe.into()),
+                _ => Ok(0),
+            }
+        }
+    }
+
+    #[test]
+    fn test_reader_streaming() {
+        let text = r#"{"a": [1, "\u00e9è"], "b": -2.5e1}  "#;
+        let mut r = Trickle { data: text.as_bytes(), fail: false };
+        let val = Value::from_json_str(text).unwrap();
+        assert_eq!(Value::from_reader(&mut r).unwrap(), val);
+
+        let mut r = Trickle { data: b"[1, 2", fail: true };
+        let err = Value::from_reader(&mut r).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+        let mut r = Trickle { data: b"[1, 2] x", fail: false };
+        let err = Value::from_reader(&mut r).unwrap_err();
+        assert_eq!(err.to_string(), "trailing characters at byte 7");
+    }
+
+    #[test]
+    fn test_writer_error() {
+        let mut buf = [0u8; 4];
+        let err = Value::str("too long").to_writer(&mut &mut buf[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(&buf, b"\"too");
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        for text in &["", "[1,]", "{\"a\" 1}", "01", "\"abc", "nul", "1 2", "\"\\x\""] {
+            assert!(Value::from_json_str(text).is_err(), "{}", text);
+        }
+        let err = Value::from_json_str("[1, ?]").unwrap_err();
+        assert_eq!(err.offset, 4);
+        assert_eq!(err.to_string(), "unexpected character at byte 4");
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        let err = Value::from_json_str(&"[".repeat(100_000)).unwrap_err();
+        assert_eq!(err.message, "nesting too deep");
+        assert_eq!(err.offset, 128);
+        let err = Value::from_json_str(&"{\"a\":".repeat(200)).unwrap_err();
+        assert_eq!(err.message, "nesting too deep");
+
+        let deepest = format!("{}{}", "[".repeat(128), "]".repeat(128));
+        assert!(Value::from_json_str(&deepest).is_ok());
+    }
+}
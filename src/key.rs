@@ -0,0 +1,71 @@
+//! Looking up table entries by a borrowed string.
+
+use std::borrow::Borrow;
+use std::hash::{ Hash, Hasher };
+
+use Value;
+
+/// A table key that is either a value or a borrowed string.
+///
+/// Values borrow as `dyn Key`, so a `HashMap<Value, _>` can be searched
+/// with a `&str` without building a `Value::String`, which allocates for
+/// long strings. A string hashes and compares like a `Value::String`
+/// holding it, and never equals a value of another kind.
+pub trait Key {
+    /// Returns the key as a value or a string.
+    fn key<'k>(&'k self) -> KeyRef<'k>;
+}
+
+/// The two forms of a `Key`.
+pub enum KeyRef<'a> {
+    Value(&'a Value),
+    Str(&'a str),
+}
+
+impl<'a> KeyRef<'a> {
+    fn as_str(&self) -> Option<&'a str> {
+        match *self {
+            KeyRef::Value(val) => val.as_str(),
+            KeyRef::Str(text) => Some(text),
+        }
+    }
+}
+
+impl Key for Value {
+    fn key<'k>(&'k self) -> KeyRef<'k> {
+        KeyRef::Value(self)
+    }
+}
+
+impl<'a> Key for &'a str {
+    fn key<'k>(&'k self) -> KeyRef<'k> {
+        KeyRef::Str(self)
+    }
+}
+
+impl<'a> Borrow<dyn Key + 'a> for Value {
+    fn borrow(&self) -> &(dyn Key + 'a) {
+        self
+    }
+}
+
+impl<'a> Hash for dyn Key + 'a {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        // `Value::String` hashes only its text, see `Hash for Value`.
+        match self.key() {
+            KeyRef::Value(val) => val.hash(state),
+            KeyRef::Str(text) => text.hash(state),
+        }
+    }
+}
+
+impl<'a> PartialEq for dyn Key + 'a {
+    fn eq(&self, other: &(dyn Key + 'a)) -> bool {
+        match (self.key(), other.key()) {
+            (KeyRef::Value(a), KeyRef::Value(b)) => a == b,
+            (a, b) => a.as_str().is_some() && a.as_str() == b.as_str(),
+        }
+    }
+}
+
+impl<'a> Eq for dyn Key + 'a {}
@@ -3,16 +3,138 @@
 
 //! A table object type for dynamical data
 
-use std::collections::HashMap;
+use std::collections::{ hash_map, BTreeMap, HashMap };
 use std::sync::Arc;
 use std::ops::{ Deref, DerefMut, Index, IndexMut };
 use std::hash::{ Hash, Hasher };
-use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::iter::FromIterator;
+use std::str::FromStr;
+use std::convert::Infallible;
+
+/// Creates a `Table` from `key => value` pairs.
+///
+/// Keys and values accept anything that implements `Into<Value>`.
+///
+/// ```
+/// #[macro_use]
+/// extern crate table;
+///
+/// # fn main() {
+/// let player = table!{
+///     "name" => "Sven",
+///     "pos" => table!{ 0 => 1.0, 1 => 2.0 },
+/// };
+/// assert_eq!(player["name"], table::Value::str("Sven"));
+/// # }
+/// ```
+#[macro_export]
+macro_rules! table {
+    () => { $crate::Table::new() };
+    ($($key:expr => $val:expr),+ $(,)?) => {{
+        let mut table = $crate::Table::new();
+        $(
+            table.insert($crate::Value::from($key), $crate::Value::from($val));
+        )+
+        table
+    }};
+}
+
+mod arith;
+mod builder;
+mod codec;
+mod convert;
+mod csv;
+mod diff;
+mod env;
+mod fingerprint;
+#[cfg(feature = "fxhash")]
+mod fx;
+mod hashed;
+mod json;
+mod key;
+mod ordered;
+#[cfg(feature = "parallel")]
+mod parallel;
+mod path;
+mod pool;
+mod render;
+#[cfg(feature = "ron")]
+mod ron;
+mod schema;
+mod snapshot;
+mod text;
+#[cfg(feature = "toml")]
+mod toml;
+mod walk;
+
+pub use builder::TableBuilder;
+pub use codec::DecodeError;
+pub use convert::FromValue;
+pub use csv::CsvError;
+pub use diff::{ Change, TableDiff };
+#[cfg(feature = "fxhash")]
+pub use fx::{ FxBuildHasher, FxHasher, FxTable };
+pub use hashed::HashedTable;
+pub use json::ParseError;
+pub use ordered::OrderedTable;
+pub use path::{ Key, PathError };
+pub use pool::StringPool;
+pub use render::RenderError;
+pub use schema::{ Schema, ValidationError };
+pub use snapshot::TableSnapshot;
+pub use text::Str;
+pub use walk::{ DepthError, KeyConflict, PathSegment };
 
 /// Represents a dynamical typed value
-#[derive(Clone, PartialEq, Eq, Debug)]
+///
+/// Numbers compare and hash by mathematical value, so `Usize(5)`, `U64(5)`,
+/// `I64(5)` and `F64(5.0)` are all equal and find the same table entry.
+///
+/// The operators `+`, `-`, `*` and `/` work on any two numbers. Integers
+/// stay integers, using `Usize` for two `Usize` operands, `U64` unless an
+/// operand is `I64`, and `I64` otherwise. A result that does not fit that
+/// variant uses another integer variant where possible. Division that is
+/// not exact and any operation with a float give `F64`. `+` also joins two
+/// strings. Other operands, integer overflow and integer division by zero
+/// give `Null`.
+///
+/// Values can not form cycles. Nested data sits behind `Arc`s without
+/// interior mutability, and `as_table_mut` copies a table that is shared
+/// before changing it, so inserting a table into itself inserts a copy.
+/// Recursive operations like `Hash`, `PartialEq` and `Debug` therefore
+/// always terminate.
+///
+/// The enum is `#[non_exhaustive]`, so new variants can be added without
+/// breaking code in other crates. A `match` on a value there needs a
+/// wildcard arm. Code that only cares about some kinds can use `kind` and
+/// the `is_*` and `as_*` accessors instead, which keep working when
+/// variants are added:
+///
+/// ```
+/// use table::{ Value, ValueKind };
+///
+/// fn describe(val: &Value) -> String {
+///     match *val {
+///         Value::Null => "nothing".into(),
+///         Value::String(ref text) => format!("text {}", text),
+///         _ if val.is_number() => format!("number {}", val),
+///         _ => format!("some {}", val.kind()),
+///     }
+/// }
+///
+/// assert_eq!(describe(&Value::from(3)), "number 3");
+/// assert_eq!(describe(&Value::from(true)), "some bool");
+/// assert_eq!(Value::from('x').kind(), ValueKind::Char);
+/// ```
+#[derive(Clone, Debug, Default)]
+#[non_exhaustive]
 pub enum Value {
     /// An empty value.
+    #[default]
     Null,
     /// A boolean value.
     Bool(bool),
@@ -24,8 +146,18 @@ pub enum Value {
     I64(i64),
     /// A 64 bit floating number.
     F64(F64),
-    /// A string.
-    String(Arc<String>),
+    /// A single character.
+    ///
+    /// A char never equals a string, even one holding just that character.
+    Char(char),
+    /// A string, stored inline when short.
+    String(Str),
+    /// Binary data.
+    ///
+    /// JSON output and `Display` write bytes as a base64 string.
+    Bytes(Arc<Vec<u8>>),
+    /// An array.
+    Array(Arc<Vec<Value>>),
     /// A table.
     Table(Arc<Table>),
 }
@@ -38,17 +170,506 @@ impl Value {
 
     /// Creates a new string value.
     pub fn str(val: &str) -> Value {
-        use std::borrow::ToOwned;
+        Value::String(Str::new(val))
+    }
 
-        Value::String(Arc::new(val.to_owned()))
+    /// Creates a new array value.
+    pub fn array(val: Vec<Value>) -> Value {
+        Value::Array(Arc::new(val))
     }
 
     /// Creates a new table value.
     pub fn table(val: Table) -> Value {
         Value::Table(Arc::new(val))
     }
+
+    /// Returns `true` if this is a null value.
+    #[inline]
+    pub fn is_null(&self) -> bool {
+        matches!(*self, Value::Null)
+    }
+
+    /// Returns `true` if this is a bool value.
+    #[inline]
+    pub fn is_bool(&self) -> bool {
+        matches!(*self, Value::Bool(_))
+    }
+
+    /// Returns `true` if this is a usize, u64, i64 or f64 value.
+    #[inline]
+    pub fn is_number(&self) -> bool {
+        matches!(*self,
+            Value::Usize(_) | Value::U64(_) | Value::I64(_) | Value::F64(_))
+    }
+
+    /// Returns `true` if this is a char value.
+    #[inline]
+    pub fn is_char(&self) -> bool {
+        matches!(*self, Value::Char(_))
+    }
+
+    /// Returns `true` if this is a string value.
+    #[inline]
+    pub fn is_string(&self) -> bool {
+        matches!(*self, Value::String(_))
+    }
+
+    /// Returns `true` if this is a bytes value.
+    #[inline]
+    pub fn is_bytes(&self) -> bool {
+        matches!(*self, Value::Bytes(_))
+    }
+
+    /// Returns `true` if this is an array value.
+    #[inline]
+    pub fn is_array(&self) -> bool {
+        matches!(*self, Value::Array(_))
+    }
+
+    /// Returns `true` if this is a table value.
+    #[inline]
+    pub fn is_table(&self) -> bool {
+        matches!(*self, Value::Table(_))
+    }
+
+    /// Returns whether the value counts as true in a condition.
+    ///
+    /// - `Null` and `Bool(false)` are false.
+    /// - Numbers equal to zero are false, including `-0.0`. `NaN` is true.
+    /// - Empty strings, bytes, arrays and tables are false.
+    /// - Everything else is true, including every char.
+    pub fn is_truthy(&self) -> bool {
+        match *self {
+            Value::Null => false,
+            Value::Bool(val) => val,
+            Value::Usize(val) => val != 0,
+            Value::U64(val) => val != 0,
+            Value::I64(val) => val != 0,
+            Value::F64(F64(val)) => val != 0.0,
+            Value::Char(_) => true,
+            Value::String(ref text) => !text.is_empty(),
+            Value::Bytes(ref bytes) => !bytes.is_empty(),
+            Value::Array(ref items) => !items.is_empty(),
+            Value::Table(ref table) => !table.is_empty(),
+        }
+    }
+
+    /// Returns the boolean, if this is a bool value.
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Returns the number, if this is a usize value.
+    pub fn as_usize(&self) -> Option<usize> {
+        match *self {
+            Value::Usize(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Returns the number, if this is a u64 value.
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::U64(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Returns the number, if this is an i64 value.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::I64(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Returns the number, if this is an f64 value.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::F64(F64(val)) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Returns any numeric value as a float.
+    ///
+    /// Integers above 2^53 in magnitude lose precision, since `f64` can
+    /// not represent all of them.
+    pub fn as_number(&self) -> Option<f64> {
+        Number::of(self).map(Number::to_f64)
+    }
+
+    /// Returns the value of any integer variant, widened to `i128`.
+    ///
+    /// Every `usize`, `u64` and `i64` fits, so nothing is lost. Floats
+    /// return `None`, even when they are whole numbers.
+    pub fn as_integer(&self) -> Option<i128> {
+        match Number::of(self)? {
+            Number::Int(x) => Some(x),
+            Number::Float(_) => None,
+        }
+    }
+
+    /// Returns the character, if this is a char value.
+    pub fn as_char(&self) -> Option<char> {
+        match *self {
+            Value::Char(val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Borrows the string, if this is a string value.
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            Value::String(ref text) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Borrows the data, if this is a bytes value.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Value::Bytes(ref bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Borrows the elements, if this is an array value.
+    pub fn as_array(&self) -> Option<&Vec<Value>> {
+        match *self {
+            Value::Array(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Mutably borrows the elements, if this is an array value.
+    ///
+    /// The array is cloned first if it is shared with other values.
+    pub fn as_array_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match *self {
+            Value::Array(ref mut val) => Some(Arc::make_mut(val)),
+            _ => None,
+        }
+    }
+
+    /// Borrows the table, if this is a table value.
+    pub fn as_table(&self) -> Option<&Table> {
+        match *self {
+            Value::Table(ref val) => Some(val),
+            _ => None,
+        }
+    }
+
+    /// Mutably borrows the table, if this is a table value.
+    ///
+    /// The table is cloned first if it is shared with other values.
+    pub fn as_table_mut(&mut self) -> Option<&mut Table> {
+        match *self {
+            Value::Table(ref mut val) => Some(Arc::make_mut(val)),
+            _ => None,
+        }
+    }
+
+    /// Iterates over the entries, if this is a table value.
+    ///
+    /// The entries are borrowed from the table and come in arbitrary
+    /// order. This allows iterating a nested table in a function that
+    /// returns an `Option`:
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::Value;
+    ///
+    /// fn count_strings(val: &Value) -> Option<usize> {
+    ///     let mut n = 0;
+    ///     for (_, val) in val.entries()? {
+    ///         if val.is_string() { n += 1; }
+    ///     }
+    ///     Some(n)
+    /// }
+    ///
+    /// # fn main() {
+    /// let val = Value::table(table!{ "a" => "x", "b" => 1, "c" => "y" });
+    /// assert_eq!(count_strings(&val), Some(2));
+    /// assert_eq!(count_strings(&Value::Null), None);
+    /// # }
+    /// ```
+    pub fn entries(&self) -> Option<impl Iterator<Item = (&Value, &Value)>> {
+        self.as_table().map(|table| table.iter())
+    }
+
+    /// Returns the number of items in a container value.
+    ///
+    /// This is the length in bytes for strings and bytes, the number of
+    /// elements for arrays and the number of entries for tables. Other
+    /// values have no length and return `None`.
+    pub fn len(&self) -> Option<usize> {
+        match *self {
+            Value::String(ref text) => Some(text.len()),
+            Value::Bytes(ref bytes) => Some(bytes.len()),
+            Value::Array(ref items) => Some(items.len()),
+            Value::Table(ref table) => Some(table.len()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether a container value is empty, see `len`.
+    pub fn is_empty(&self) -> Option<bool> {
+        self.len().map(|len| len == 0)
+    }
+
+    /// Returns the number as an index, if it is a whole number in range.
+    fn to_index(&self) -> Option<usize> {
+        match Number::of(self)? {
+            Number::Int(x) => usize::try_from(x).ok(),
+            Number::Float(x) => {
+                if x.fract() == 0.0 && x >= 0.0 && x < usize::MAX as f64 {
+                    Some(x as usize)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Applies a JSON merge patch as defined by RFC 7386.
+    ///
+    /// A table patch turns this value into a table if it is not one and
+    /// is applied like `Table::merge_patch`. Any other patch replaces the
+    /// value.
+    pub fn merge_patch(&mut self, patch: &Value) {
+        if !patch.is_table() {
+            *self = patch.clone();
+            return;
+        }
+        if !self.is_table() {
+            *self = Value::table(Table::new());
+        }
+        if let Value::Table(ref mut table) = *self {
+            Arc::make_mut(table).merge_patch(patch);
+        }
+    }
+
+    /// Compares values, allowing floats to differ by up to `epsilon`.
+    ///
+    /// When either side is an `F64`, numbers are compared as floats within
+    /// `epsilon`, and NaN is only close to NaN. Other numbers compare by
+    /// value. Tables match if they have the same keys and the values under
+    /// each key are close, so a missing key on either side makes them
+    /// differ. Arrays match if they have the same length and their elements
+    /// are close. All other values must be equal.
+    pub fn approx_eq(&self, other: &Value, epsilon: f64) -> bool {
+        match (self, other) {
+            (&Value::Table(ref a), &Value::Table(ref b)) => {
+                a.len() == b.len() && a.iter().all(|(key, val)| {
+                    b.get(key).is_some_and(|other| val.approx_eq(other, epsilon))
+                })
+            }
+            (&Value::Array(ref a), &Value::Array(ref b)) => {
+                a.len() == b.len() &&
+                a.iter().zip(b.iter()).all(|(a, b)| a.approx_eq(b, epsilon))
+            }
+            _ => match (Number::of(self), Number::of(other)) {
+                (Some(Number::Int(_)), Some(Number::Int(_))) => self == other,
+                (Some(a), Some(b)) => {
+                    let (a, b) = (a.to_f64(), b.to_f64());
+                    (a.is_nan() && b.is_nan()) || (a - b).abs() <= epsilon
+                }
+                _ => self == other,
+            }
+        }
+    }
+
+    /// Returns the kind of value, without the payload.
+    pub const fn kind(&self) -> ValueKind {
+        match *self {
+            Value::Null => ValueKind::Null,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::Usize(_) => ValueKind::Usize,
+            Value::U64(_) => ValueKind::U64,
+            Value::I64(_) => ValueKind::I64,
+            Value::F64(_) => ValueKind::F64,
+            Value::Char(_) => ValueKind::Char,
+            Value::String(_) => ValueKind::String,
+            Value::Bytes(_) => ValueKind::Bytes,
+            Value::Array(_) => ValueKind::Array,
+            Value::Table(_) => ValueKind::Table,
+        }
+    }
+
+    /// Returns the name of the kind of value, like `"string"`.
+    ///
+    /// This is the same as `self.kind().name()`, and is meant for log and
+    /// error messages.
+    pub const fn type_name(&self) -> &'static str {
+        self.kind().name()
+    }
+
+    /// Parses text into the narrowest fitting kind of value.
+    ///
+    /// The rules are tried in this order, and the first match wins:
+    ///
+    /// - `null` becomes `Null`.
+    /// - `true` and `false` become `Bool`.
+    /// - Integers become `Usize`, then `U64` and then `I64`, whichever
+    ///   fits first.
+    /// - Other numbers that contain a digit become `F64`, so `1.5` and
+    ///   `1e3` do but `inf` and `NaN` do not.
+    /// - Everything else becomes a `String`.
+    ///
+    /// Surrounding whitespace is not trimmed, so `" 1"` is a string.
+    pub fn parse_typed(text: &str) -> Value {
+        match text {
+            "null" => return Value::Null,
+            "true" => return Value::Bool(true),
+            "false" => return Value::Bool(false),
+            _ => {}
+        }
+        if let Ok(x) = text.parse() {
+            return Value::Usize(x);
+        }
+        if let Ok(x) = text.parse() {
+            return Value::U64(x);
+        }
+        if let Ok(x) = text.parse() {
+            return Value::I64(x);
+        }
+        if text.bytes().any(|b| b.is_ascii_digit()) {
+            if let Ok(x) = text.parse() {
+                return Value::f64(x);
+            }
+        }
+        Value::str(text)
+    }
+
+    /// Returns the value with numbers in a single canonical form.
+    ///
+    /// Floats that hold a whole number which fits an integer variant
+    /// become integers. Integers become `Usize` when non-negative and
+    /// `I64` when negative, using `U64` only for values that do not fit
+    /// `usize`. Other floats, including NaN and infinities, stay `F64`.
+    /// Tables, including their keys, and arrays are canonicalized
+    /// recursively, so equal values canonicalize to identical structures.
+    pub fn canonicalize(&self) -> Value {
+        match *self {
+            Value::Usize(_) | Value::U64(_) | Value::I64(_) | Value::F64(_) => {
+                canonical_number(self)
+            }
+            Value::Array(ref items) => {
+                Value::array(items.iter().map(Value::canonicalize).collect())
+            }
+            Value::Table(ref table) => Value::table(table.canonicalize()),
+            _ => self.clone(),
+        }
+    }
+
+    /// Returns the value in canonical form, optionally without `Null`
+    /// table entries.
+    ///
+    /// Numbers are narrowed like in `canonicalize`, so whole floats become
+    /// integers and integers use the first of `Usize`, `I64` and `U64`
+    /// that holds them. When `drop_nulls` is set, entries with `Null`
+    /// values are also removed from tables at every depth, which
+    /// `canonicalize` never does. Array elements are kept even when they
+    /// are `Null`, since removing them would shift the following indices.
+    /// Tables left empty by pruning stay in place.
+    pub fn simplify(&self, drop_nulls: bool) -> Value {
+        match *self {
+            Value::Array(ref items) => {
+                Value::array(items.iter().map(|item| item.simplify(drop_nulls)).collect())
+            }
+            Value::Table(ref table) => {
+                Value::table(table.iter()
+                    .filter(|&(_, val)| !(drop_nulls && val.is_null()))
+                    .map(|(key, val)| (key.canonicalize(), val.simplify(drop_nulls)))
+                    .collect())
+            }
+            _ => self.canonicalize(),
+        }
+    }
+
+    /// Estimates the heap memory owned by the value, in bytes.
+    ///
+    /// This counts string and vector capacities, the entries of nested
+    /// tables and the reference counts of each `Arc`, but not the
+    /// `size_of::<Value>()` bytes of the value itself. Data shared through
+    /// an `Arc` is counted once per reference, so a table holding the same
+    /// nested table twice counts it twice. The size of a table is an
+    /// estimate, see `Table::memory_usage`.
+    pub fn memory_usage(&self) -> usize {
+        use std::mem::size_of;
+
+        let counts = 2 * size_of::<usize>();
+        match *self {
+            Value::String(ref text) => text.memory_usage(),
+            Value::Bytes(ref bytes) => {
+                counts + size_of::<Vec<u8>>() + bytes.capacity()
+            }
+            Value::Array(ref items) => {
+                counts + size_of::<Vec<Value>>() +
+                items.capacity() * size_of::<Value>() +
+                items.iter().map(Value::memory_usage).sum::<usize>()
+            }
+            Value::Table(ref table) => {
+                counts + size_of::<Table>() + table.memory_usage()
+            }
+            _ => 0,
+        }
+    }
+
+    /// Clones the value without sharing any data with the original.
+    ///
+    /// `clone` is cheap because long strings, bytes, arrays and tables are
+    /// shared through `Arc`, and they are only copied once one side is changed.
+    /// `deep_clone` instead copies all nested data up front, which takes
+    /// time and memory proportional to the whole value.
+    pub fn deep_clone(&self) -> Value {
+        match *self {
+            Value::String(ref val) => Value::str(val),
+            Value::Bytes(ref bytes) => Value::Bytes(Arc::new((**bytes).clone())),
+            Value::Array(ref items) => {
+                Value::Array(Arc::new(items.iter().map(Value::deep_clone).collect()))
+            }
+            Value::Table(ref table) => Value::table(table.deep_clone()),
+            _ => self.clone(),
+        }
+    }
+}
+
+/// Parses with `Value::parse_typed`, which never fails.
+impl FromStr for Value {
+    type Err = Infallible;
+
+    fn from_str(text: &str) -> Result<Value, Infallible> {
+        Ok(Value::parse_typed(text))
+    }
+}
+
+impl From<bool> for Value {
+    fn from(val: bool) -> Value {
+        Value::Bool(val)
+    }
+}
+
+// Smaller numbers widen losslessly to the 64 bit variant of the same sign.
+macro_rules! from_widening {
+    ($($ty:ty => $variant:ident($target:ty)),*) => {$(
+        impl From<$ty> for Value {
+            fn from(val: $ty) -> Value {
+                Value::$variant(val as $target)
+            }
+        }
+    )*}
 }
 
+from_widening!(u8 => U64(u64), u16 => U64(u64), u32 => U64(u64),
+    i8 => I64(i64), i16 => I64(i64), i32 => I64(i64));
+
 impl From<usize> for Value {
     fn from(val: usize) -> Value {
         Value::Usize(val)
@@ -73,15 +694,45 @@ impl From<f64> for Value {
     }
 }
 
+impl From<f32> for Value {
+    fn from(val: f32) -> Value {
+        Value::f64(val as f64)
+    }
+}
+
+impl From<char> for Value {
+    fn from(val: char) -> Value {
+        Value::Char(val)
+    }
+}
+
 impl<'a> From<&'a str> for Value {
     fn from(val: &'a str) -> Value {
-        Value::String(Arc::new(val.into()))
+        Value::str(val)
     }
 }
 
 impl From<String> for Value {
     fn from(val: String) -> Value {
-        Value::String(Arc::new(val))
+        Value::String(Str::from(val))
+    }
+}
+
+impl From<Vec<u8>> for Value {
+    fn from(val: Vec<u8>) -> Value {
+        Value::Bytes(Arc::new(val))
+    }
+}
+
+impl<'a> From<&'a [u8]> for Value {
+    fn from(val: &'a [u8]) -> Value {
+        Value::Bytes(Arc::new(val.to_vec()))
+    }
+}
+
+impl From<Vec<Value>> for Value {
+    fn from(val: Vec<Value>) -> Value {
+        Value::Array(Arc::new(val))
     }
 }
 
@@ -91,233 +742,2550 @@ impl From<Table> for Value {
     }
 }
 
-impl Hash for Value {
-    fn hash<S>(&self, state: &mut S) where S: Hasher {
-        match self {
-            &Value::String(ref text) => text.hash(state),
-            &Value::Null => 0.hash(state),
-            &Value::Bool(val) => val.hash(state),
-            &Value::Usize(val) => val.hash(state),
-            &Value::U64(val) => val.hash(state),
-            &Value::I64(val) => val.hash(state),
-            &Value::F64(val) => val.hash(state),
-            &Value::Table(ref val) => val.hash(state),
-        }
+/// Converts `None` to `Null` and `Some` to the converted value.
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(val: Option<T>) -> Value {
+        val.map_or(Value::Null, Into::into)
     }
 }
 
-impl Borrow<str> for Value {
-    fn borrow(&self) -> &str {
+/// Describes the kind of a value, without the payload.
+///
+/// Like `Value`, this is `#[non_exhaustive]` and gains a variant whenever
+/// `Value` does.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug)]
+#[non_exhaustive]
+pub enum ValueKind {
+    /// An empty value.
+    Null,
+    /// A boolean value.
+    Bool,
+    /// A pointer sized integer.
+    Usize,
+    /// A 64 bit unsigned integer.
+    U64,
+    /// A 64 bit signed integer.
+    I64,
+    /// A 64 bit floating number.
+    F64,
+    /// A single character.
+    Char,
+    /// A string.
+    String,
+    /// Binary data.
+    Bytes,
+    /// An array.
+    Array,
+    /// A table.
+    Table,
+}
+
+impl ValueKind {
+    /// Returns the lowercase name of the kind, like `"string"`.
+    pub const fn name(self) -> &'static str {
         match self {
-            &Value::String(ref text) => {
-                &text
-            }
-            _ => ""
+            ValueKind::Null => "null",
+            ValueKind::Bool => "bool",
+            ValueKind::Usize => "usize",
+            ValueKind::U64 => "u64",
+            ValueKind::I64 => "i64",
+            ValueKind::F64 => "f64",
+            ValueKind::Char => "char",
+            ValueKind::String => "string",
+            ValueKind::Bytes => "bytes",
+            ValueKind::Array => "array",
+            ValueKind::Table => "table",
         }
     }
 }
 
-/// Wrapper for f64
-#[derive(Copy, Clone, PartialEq, Debug)]
-pub struct F64(pub f64);
-
-impl Eq for F64 {}
-
-impl Hash for F64 {
-    fn hash<S>(&self, state: &mut S) where S: Hasher {
-        let val = self.0 as u64;
-        val.hash(state)
+impl fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
     }
 }
 
-impl Deref for F64 {
-    type Target = f64;
+/// An error returned when a value does not hold the requested type.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ValueError {
+    /// The kind that was requested.
+    pub expected: ValueKind,
+    /// The kind that was found.
+    pub found: ValueKind,
+}
 
-    fn deref<'a>(&'a self) -> &'a f64 {
-        &self.0
+impl ValueError {
+    fn new(expected: ValueKind, found: &Value) -> ValueError {
+        ValueError { expected, found: found.kind() }
     }
 }
 
-impl DerefMut for F64 {
-    fn deref_mut<'a>(&'a mut self) -> &'a mut f64 {
-        &mut self.0
+impl fmt::Display for ValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found)
     }
 }
 
-/// The table object
-#[derive(Clone, PartialEq, Eq, Debug)]
-pub struct Table(pub HashMap<Value, Value>);
+impl Error for ValueError {}
 
-impl Hash for Table {
-    fn hash<S>(&self, state: &mut S) where S: Hasher {
-        for (key, val) in self.0.iter() {
-            key.hash(state);
-            val.hash(state);
-        }
+// Panicking counterparts of the `as_*` accessors.
+macro_rules! expect_accessors {
+    ($($name:ident, $as_:ident, $ty:ty, $kind:expr, $doc:expr;)*) => {
+        impl Value {$(
+            #[doc = $doc]
+            ///
+            /// # Panics
+            ///
+            /// Panics with a message like `expected string, found table` if
+            /// the value is of another kind.
+            #[track_caller]
+            pub fn $name(&self) -> $ty {
+                match self.$as_() {
+                    Some(val) => val,
+                    None => panic!("{}", ValueError::new($kind, self)),
+                }
+            }
+        )*}
     }
 }
 
-impl Deref for Table {
-    type Target = HashMap<Value, Value>;
+expect_accessors! {
+    expect_bool, as_bool, bool, ValueKind::Bool,
+        "Returns the boolean of a bool value.";
+    expect_usize, as_usize, usize, ValueKind::Usize,
+        "Returns the integer of a usize value.";
+    expect_u64, as_u64, u64, ValueKind::U64,
+        "Returns the integer of a u64 value.";
+    expect_i64, as_i64, i64, ValueKind::I64,
+        "Returns the integer of an i64 value.";
+    expect_f64, as_f64, f64, ValueKind::F64,
+        "Returns the number of an f64 value.";
+    expect_char, as_char, char, ValueKind::Char,
+        "Returns the character of a char value.";
+    expect_str, as_str, &str, ValueKind::String,
+        "Borrows the string of a string value.";
+    expect_bytes, as_bytes, &[u8], ValueKind::Bytes,
+        "Borrows the data of a bytes value.";
+    expect_array, as_array, &Vec<Value>, ValueKind::Array,
+        "Borrows the elements of an array value.";
+    expect_table, as_table, &Table, ValueKind::Table,
+        "Borrows the table of a table value.";
+}
 
-    fn deref<'a>(&'a self) -> &'a HashMap<Value, Value> {
-        &self.0
+// Narrowing integer conversions that check or clamp the range.
+macro_rules! narrowing_conversions {
+    ($($checked:ident, $saturating:ident, $ty:ident;)*) => {
+        impl Value {$(
+            #[doc = concat!("Converts an integer value to `", stringify!($ty),
+                "`, if it is in range.")]
+            ///
+            /// Any integer variant is accepted. Floats and other kinds
+            /// return `None`.
+            pub fn $checked(&self) -> Option<$ty> {
+                $ty::try_from(self.as_integer()?).ok()
+            }
+
+            #[doc = concat!("Converts an integer value to `", stringify!($ty),
+                "`, clamping it to the range of the type.")]
+            ///
+            /// Any integer variant is accepted. Floats and other kinds
+            /// return `None`.
+            pub fn $saturating(&self) -> Option<$ty> {
+                let x = self.as_integer()?;
+                Some(x.clamp($ty::MIN as i128, $ty::MAX as i128) as $ty)
+            }
+        )*}
     }
 }
 
-impl DerefMut for Table {
-    fn deref_mut<'a>(&'a mut self) -> &'a mut HashMap<Value, Value> {
-        &mut self.0
-    }
+narrowing_conversions! {
+    to_u8_checked, to_u8_saturating, u8;
+    to_u16_checked, to_u16_saturating, u16;
+    to_u32_checked, to_u32_saturating, u32;
+    to_u64_checked, to_u64_saturating, u64;
+    to_usize_checked, to_usize_saturating, usize;
+    to_i8_checked, to_i8_saturating, i8;
+    to_i16_checked, to_i16_saturating, i16;
+    to_i32_checked, to_i32_saturating, i32;
+    to_i64_checked, to_i64_saturating, i64;
 }
 
-impl Index<Value> for Table {
-    type Output = Value;
+macro_rules! try_from_value {
+    ($ty:ty, $kind:expr, $pat:pat => $val:expr) => {
+        impl TryFrom<Value> for $ty {
+            type Error = ValueError;
 
-    fn index<'a>(&'a self, index: Value) -> &'a Value {
-        self.0.get(&index).unwrap()
-    }
-}
+            fn try_from(val: Value) -> Result<$ty, ValueError> {
+                <$ty>::try_from(&val)
+            }
+        }
 
-impl Index<usize> for Table {
-    type Output = Value;
+        impl<'a> TryFrom<&'a Value> for $ty {
+            type Error = ValueError;
 
-    fn index<'a>(&'a self, index: usize) -> &'a Value {
-        self.0.get(&Value::Usize(index)).unwrap()
+            fn try_from(val: &'a Value) -> Result<$ty, ValueError> {
+                match *val {
+                    $pat => Ok($val),
+                    _ => Err(ValueError::new($kind, val)),
+                }
+            }
+        }
     }
 }
 
-impl<'b> Index<&'b str> for Table {
-    type Output = Value;
+try_from_value!(usize, ValueKind::Usize, Value::Usize(x) => x);
+try_from_value!(u64, ValueKind::U64, Value::U64(x) => x);
+try_from_value!(i64, ValueKind::I64, Value::I64(x) => x);
+try_from_value!(f64, ValueKind::F64, Value::F64(F64(x)) => x);
+try_from_value!(bool, ValueKind::Bool, Value::Bool(x) => x);
+try_from_value!(char, ValueKind::Char, Value::Char(x) => x);
 
-    fn index<'a>(&'a self, index: &'b str) -> &'a Value {
-        self.0.get(index).unwrap()
+impl TryFrom<Value> for String {
+    type Error = ValueError;
+
+    fn try_from(val: Value) -> Result<String, ValueError> {
+        match val {
+            Value::String(text) => Ok(text.into_string()),
+            _ => Err(ValueError::new(ValueKind::String, &val)),
+        }
     }
 }
 
-impl IndexMut<Value> for Table {
-    fn index_mut<'a>(&'a mut self, index: Value) -> &'a mut Value {
-        use std::collections::hash_map::Entry;
+impl<'a> TryFrom<&'a Value> for String {
+    type Error = ValueError;
 
-        match self.0.entry(index.clone()) {
-            Entry::Occupied(_) => {},
-            Entry::Vacant(x) => {x.insert(Value::Null);}
-        };
-        self.0.get_mut(&index).unwrap()
+    fn try_from(val: &'a Value) -> Result<String, ValueError> {
+        match *val {
+            Value::String(ref text) => Ok(text.as_str().to_owned()),
+            _ => Err(ValueError::new(ValueKind::String, val)),
+        }
     }
 }
 
-impl IndexMut<usize> for Table {
-    fn index_mut<'a>(&'a mut self, index: usize) -> &'a mut Value {
-        use std::collections::hash_map::Entry;
+/// Numbers are put in a common form for comparison across variants.
+#[derive(Copy, Clone)]
+enum Number {
+    Int(i128),
+    Float(f64),
+}
 
-        match self.0.entry(Value::Usize(index)) {
-            Entry::Occupied(_) => {},
-            Entry::Vacant(x) => {x.insert(Value::Null);}
-        };
-        self.0.get_mut(&Value::Usize(index)).unwrap()
+fn eq_ignoring_nulls(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (&Value::Table(ref a), &Value::Table(ref b)) => a.eq_ignoring_nulls(b),
+        (&Value::Array(ref a), &Value::Array(ref b)) => {
+            a.len() == b.len() &&
+            a.iter().zip(b.iter()).all(|(a, b)| eq_ignoring_nulls(a, b))
+        }
+        _ => a == b,
     }
 }
 
-impl<'b> IndexMut<&'b str> for Table {
-    fn index_mut<'a>(&'a mut self, index: &'b str) -> &'a mut Value {
-        use std::borrow::ToOwned;
-
-        if !self.0.contains_key(index) {
-            self.insert(Value::String(Arc::new((*index).to_owned())), 
-                Value::Null);
+fn canonical_number(val: &Value) -> Value {
+    let x = match Number::of(val) {
+        Some(Number::Int(x)) => x,
+        Some(Number::Float(x)) => {
+            let range = i64::MIN as f64..u64::MAX as f64;
+            if x.fract() != 0.0 || !range.contains(&x) {
+                return val.clone();
+            }
+            x as i128
         }
-        self.0.get_mut(index).unwrap()
+        None => return val.clone(),
+    };
+    if let Ok(x) = usize::try_from(x) {
+        Value::Usize(x)
+    } else if let Ok(x) = i64::try_from(x) {
+        Value::I64(x)
+    } else {
+        Value::U64(x as u64)
     }
 }
 
-impl Table {
-    /// Creates new table.
-    pub fn new() -> Table {
-        Table(HashMap::new())
+impl Number {
+    fn of(val: &Value) -> Option<Number> {
+        match *val {
+            Value::Usize(x) => Some(Number::Int(x as i128)),
+            Value::U64(x) => Some(Number::Int(x as i128)),
+            Value::I64(x) => Some(Number::Int(x as i128)),
+            Value::F64(F64(x)) => Some(Number::Float(x)),
+            _ => None,
+        }
     }
 
-    /// Creates new table with capacity.
-    pub fn with_capacity(capacity: usize) -> Table {
-        Table(HashMap::with_capacity(capacity))
+    fn to_f64(self) -> f64 {
+        match self {
+            Number::Int(x) => x as f64,
+            Number::Float(x) => x,
+        }
     }
-}
 
-#[cfg(test)]
+    fn cmp(self, other: Number) -> Ordering {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => a.cmp(&b),
+            (Number::Float(a), Number::Float(b)) => F64(a).cmp(&F64(b)),
+            (Number::Int(a), Number::Float(b)) => Number::cmp_int_float(a, b),
+            (Number::Float(a), Number::Int(b)) => {
+                Number::cmp_int_float(b, a).reverse()
+            }
+        }
+    }
+
+    fn hash<S>(self, state: &mut S) where S: Hasher {
+        match self {
+            Number::Int(x) => x.hash(state),
+            Number::Float(x) => {
+                // Whole floats in the integer range must hash like integers.
+                let range = i64::MIN as f64..u64::MAX as f64;
+                if x.fract() == 0.0 && range.contains(&x) {
+                    (x as i128).hash(state)
+                } else {
+                    F64(x).hash(state)
+                }
+            }
+        }
+    }
+
+    fn cmp_int_float(a: i128, b: f64) -> Ordering {
+        if b.is_nan() {
+            return Ordering::Less;
+        }
+        // Integers fit in 65 bits, so saturating casts of large floats
+        // still compare correctly.
+        let whole = b.trunc();
+        a.cmp(&(whole as i128)).then(F64(whole).cmp(&F64(b)))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        match (self, other) {
+            (&Value::Null, &Value::Null) => true,
+            (&Value::Bool(a), &Value::Bool(b)) => a == b,
+            (&Value::Char(a), &Value::Char(b)) => a == b,
+            (&Value::String(ref a), &Value::String(ref b)) => a == b,
+            (&Value::Bytes(ref a), &Value::Bytes(ref b)) => a == b,
+            (&Value::Array(ref a), &Value::Array(ref b)) => a == b,
+            (&Value::Table(ref a), &Value::Table(ref b)) => a == b,
+            _ => match (Number::of(self), Number::of(other)) {
+                (Some(a), Some(b)) => a.cmp(b) == Ordering::Equal,
+                _ => false,
+            }
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl PartialEq<str> for Value {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == Some(other)
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Value {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == Some(*other)
+    }
+}
+
+impl PartialEq<Value> for str {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+
+impl<'a> PartialEq<Value> for &'a str {
+    fn eq(&self, other: &Value) -> bool {
+        other == self
+    }
+}
+
+// Numbers compare by value like `Value` does, so `Value::Usize(1) == 1i64`.
+macro_rules! partial_eq_primitive {
+    ($($ty:ty),*) => {$(
+        impl PartialEq<$ty> for Value {
+            fn eq(&self, other: &$ty) -> bool {
+                let other = Value::from(*other);
+                *self == other
+            }
+        }
+
+        impl PartialEq<Value> for $ty {
+            fn eq(&self, other: &Value) -> bool {
+                other == self
+            }
+        }
+    )*}
+}
+
+partial_eq_primitive!(bool, usize, u64, i64, f64, char);
+
+impl Hash for Value {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        match *self {
+            Value::String(ref text) => text.hash(state),
+            Value::Null => 0.hash(state),
+            Value::Bool(val) => val.hash(state),
+            Value::Char(val) => val.hash(state),
+            Value::Usize(_) | Value::U64(_) | Value::I64(_) | Value::F64(_) => {
+                Number::of(self).unwrap().hash(state)
+            }
+            Value::Bytes(ref val) => val.hash(state),
+            Value::Array(ref val) => val.hash(state),
+            Value::Table(ref val) => val.hash(state),
+        }
+    }
+}
+
+impl Value {
+    fn fmt_nested(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        match *self {
+            Value::Null => f.write_str("null"),
+            Value::Bool(val) => write!(f, "{}", val),
+            Value::Usize(val) => write!(f, "{}", val),
+            Value::U64(val) => write!(f, "{}", val),
+            Value::I64(val) => write!(f, "{}", val),
+            Value::F64(val) => write!(f, "{}", val.0),
+            Value::Char(val) => write!(f, "{:?}", val),
+            Value::String(ref text) => write!(f, "{:?}", text),
+            Value::Bytes(ref bytes) => write!(f, "\"{}\"", codec::encode_base64(bytes)),
+            Value::Array(ref val) => {
+                if val.is_empty() {
+                    return f.write_str("[]");
+                }
+                let pretty = f.alternate();
+                f.write_str("[")?;
+                for (i, item) in val.iter().enumerate() {
+                    if pretty {
+                        fmt_newline(f, depth + 1)?;
+                    } else if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    item.fmt_nested(f, depth + 1)?;
+                    if pretty {
+                        f.write_str(",")?;
+                    }
+                }
+                if pretty {
+                    fmt_newline(f, depth)?;
+                }
+                f.write_str("]")
+            }
+            Value::Table(ref val) => val.fmt_nested(f, depth),
+        }
+    }
+}
+
+/// Writes chars and strings without quotes and other values like `Table`
+/// does, where nested chars and strings are quoted. Bytes are written as
+/// base64, quoted like strings.
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Value::Char(val) => write!(f, "{}", val),
+            Value::String(ref text) => f.write_str(text),
+            Value::Bytes(ref bytes) => f.write_str(&codec::encode_base64(bytes)),
+            _ => self.fmt_nested(f, 0),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders values first by kind, then by payload.
+///
+/// The kinds are ordered as
+/// `Null < Bool < number < Char < String < Bytes < Array < Table`, where
+/// `Usize`, `U64`, `I64` and `F64` all count as numbers and are compared by
+/// mathematical value, consistent with `PartialEq`.
+/// Among floats, NaN sorts after positive infinity. Chars are ordered by
+/// code point, strings lexicographically and tables by their entries sorted by key.
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        fn rank(val: &Value) -> u8 {
+            match *val {
+                Value::Null => 0,
+                Value::Bool(_) => 1,
+                Value::Usize(_) | Value::U64(_) |
+                Value::I64(_) | Value::F64(_) => 2,
+                Value::Char(_) => 3,
+                Value::String(_) => 4,
+                Value::Bytes(_) => 5,
+                Value::Array(_) => 6,
+                Value::Table(_) => 7,
+            }
+        }
+
+        match (self, other) {
+            (&Value::Bool(a), &Value::Bool(b)) => a.cmp(&b),
+            (&Value::Char(a), &Value::Char(b)) => a.cmp(&b),
+            (&Value::String(ref a), &Value::String(ref b)) => a.cmp(b),
+            (&Value::Bytes(ref a), &Value::Bytes(ref b)) => a.cmp(b),
+            (&Value::Array(ref a), &Value::Array(ref b)) => a.cmp(b),
+            (&Value::Table(ref a), &Value::Table(ref b)) => a.cmp(b),
+            _ => match (Number::of(self), Number::of(other)) {
+                (Some(a), Some(b)) => a.cmp(b),
+                _ => rank(self).cmp(&rank(other)),
+            }
+        }
+    }
+}
+
+/// Wrapper for f64
+///
+/// Unlike `f64`, all NaNs compare equal to each other, which makes `F64`
+/// usable as a hash key. Negative and positive zero are also equal.
+#[derive(Copy, Clone, Debug)]
+pub struct F64(pub f64);
+
+impl PartialEq for F64 {
+    fn eq(&self, other: &F64) -> bool {
+        self.0 == other.0 || (self.0.is_nan() && other.0.is_nan())
+    }
+}
+
+impl Eq for F64 {}
+
+impl PartialOrd for F64 {
+    fn partial_cmp(&self, other: &F64) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders by `f64::total_cmp` after canonicalizing zeros and NaNs.
+///
+/// Negative zero is equal to positive zero, and all NaNs are equal and
+/// sort after positive infinity.
+impl Ord for F64 {
+    fn cmp(&self, other: &F64) -> Ordering {
+        let a = f64::from_bits(self.canonical_bits());
+        let b = f64::from_bits(other.canonical_bits());
+        a.total_cmp(&b)
+    }
+}
+
+impl F64 {
+    /// Returns the bit pattern used for hashing.
+    ///
+    /// Negative zero maps to positive zero and every NaN maps to the same
+    /// quiet NaN, so values that compare equal also hash equal.
+    fn canonical_bits(self) -> u64 {
+        if self.0.is_nan() {
+            f64::NAN.to_bits()
+        } else if self.0 == 0.0 {
+            0
+        } else {
+            self.0.to_bits()
+        }
+    }
+}
+
+impl Hash for F64 {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        self.canonical_bits().hash(state)
+    }
+}
+
+impl Deref for F64 {
+    type Target = f64;
+
+    fn deref<'a>(&'a self) -> &'a f64 {
+        &self.0
+    }
+}
+
+impl DerefMut for F64 {
+    fn deref_mut<'a>(&'a mut self) -> &'a mut f64 {
+        &mut self.0
+    }
+}
+
+/// The table object
+#[derive(Clone, PartialEq, Eq)]
+pub struct Table(pub HashMap<Value, Value>);
+
+impl Hash for Table {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        hash_entries(self.0.iter(), state);
+    }
+}
+
+/// Hashes entries so that the result does not depend on their order.
+fn hash_entries<'a, I, S>(entries: I, state: &mut S)
+    where I: Iterator<Item = (&'a Value, &'a Value)>, S: Hasher
+{
+    // Each entry is hashed on its own and the results are combined with a
+    // commutative sum.
+    let mut len = 0;
+    let mut sum: u64 = 0;
+    for entry in entries {
+        let mut hasher = hash_map::DefaultHasher::new();
+        entry.hash(&mut hasher);
+        sum = sum.wrapping_add(hasher.finish());
+        len += 1;
+    }
+    state.write_usize(len);
+    state.write_u64(sum);
+}
+
+impl Table {
+    fn sorted_entries(&self) -> Vec<(&Value, &Value)> {
+        let mut entries: Vec<_> = self.0.iter().collect();
+        entries.sort();
+        entries
+    }
+
+    fn fmt_nested(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        fmt_entries(f, self.sorted_entries(), depth)
+    }
+}
+
+/// Writes entries as `{ key: value, ... }` in the given order.
+fn fmt_entries(
+    f: &mut fmt::Formatter,
+    entries: Vec<(&Value, &Value)>,
+    depth: usize
+) -> fmt::Result {
+    if entries.is_empty() {
+        return f.write_str("{}");
+    }
+    let pretty = f.alternate();
+    f.write_str("{")?;
+    for (i, (key, val)) in entries.into_iter().enumerate() {
+        if pretty {
+            fmt_newline(f, depth + 1)?;
+        } else {
+            f.write_str(if i == 0 { " " } else { ", " })?;
+        }
+        key.fmt_nested(f, depth + 1)?;
+        f.write_str(": ")?;
+        val.fmt_nested(f, depth + 1)?;
+        if pretty {
+            f.write_str(",")?;
+        }
+    }
+    if pretty {
+        fmt_newline(f, depth)?;
+    } else {
+        f.write_str(" ")?;
+    }
+    f.write_str("}")
+}
+
+/// Writes the table as `{ key: value, ... }` with entries sorted by key.
+///
+/// The alternate form `{:#}` puts each entry on its own indented line.
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.fmt_nested(f, 0)
+    }
+}
+
+/// Writes the table like a derived `Debug` would, but with entries sorted
+/// by key so that the output is deterministic.
+///
+/// The alternate form `{:#?}` indents nested tables.
+impl fmt::Debug for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        struct Entries<'a>(&'a Table);
+
+        impl<'a> fmt::Debug for Entries<'a> {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.debug_map().entries(self.0.sorted_entries()).finish()
+            }
+        }
+
+        f.debug_tuple("Table").field(&Entries(self)).finish()
+    }
+}
+
+fn fmt_newline(f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+    f.write_str("\n")?;
+    for _ in 0..depth {
+        f.write_str("    ")?;
+    }
+    Ok(())
+}
+
+impl PartialOrd for Table {
+    fn partial_cmp(&self, other: &Table) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Orders tables by their entries sorted by key.
+impl Ord for Table {
+    fn cmp(&self, other: &Table) -> Ordering {
+        self.sorted_entries().cmp(&other.sorted_entries())
+    }
+}
+
+impl Deref for Table {
+    type Target = HashMap<Value, Value>;
+
+    fn deref<'a>(&'a self) -> &'a HashMap<Value, Value> {
+        &self.0
+    }
+}
+
+impl DerefMut for Table {
+    fn deref_mut<'a>(&'a mut self) -> &'a mut HashMap<Value, Value> {
+        &mut self.0
+    }
+}
+
+impl AsRef<HashMap<Value, Value>> for Table {
+    fn as_ref(&self) -> &HashMap<Value, Value> {
+        &self.0
+    }
+}
+
+impl AsMut<HashMap<Value, Value>> for Table {
+    fn as_mut(&mut self) -> &mut HashMap<Value, Value> {
+        &mut self.0
+    }
+}
+
+/// Converts the keys and values of a map with any hasher.
+///
+/// The entries are moved into a new map, also when they already are
+/// values, since the table uses the default hasher.
+impl<K, V, S> From<HashMap<K, V, S>> for Table
+    where K: Into<Value>, V: Into<Value>
+{
+    fn from(map: HashMap<K, V, S>) -> Table {
+        map.into_iter().collect()
+    }
+}
+
+/// Converts the keys and values of an ordered map.
+impl<K, V> From<BTreeMap<K, V>> for Table
+    where K: Into<Value>, V: Into<Value>
+{
+    fn from(map: BTreeMap<K, V>) -> Table {
+        map.into_iter().collect()
+    }
+}
+
+impl Index<Value> for Table {
+    type Output = Value;
+
+    fn index<'a>(&'a self, index: Value) -> &'a Value {
+        self.get_value(&index).unwrap()
+    }
+}
+
+impl Index<usize> for Table {
+    type Output = Value;
+
+    fn index<'a>(&'a self, index: usize) -> &'a Value {
+        self.get_usize(index).unwrap()
+    }
+}
+
+impl<'b> Index<&'b str> for Table {
+    type Output = Value;
+
+    fn index<'a>(&'a self, index: &'b str) -> &'a Value {
+        self.get_str(index).unwrap()
+    }
+}
+
+impl IndexMut<Value> for Table {
+    fn index_mut<'a>(&'a mut self, index: Value) -> &'a mut Value {
+        use std::collections::hash_map::Entry;
+
+        match self.0.entry(index.clone()) {
+            Entry::Occupied(_) => {},
+            Entry::Vacant(x) => {x.insert(Value::Null);}
+        };
+        self.0.get_mut(&index).unwrap()
+    }
+}
+
+impl IndexMut<usize> for Table {
+    fn index_mut<'a>(&'a mut self, index: usize) -> &'a mut Value {
+        use std::collections::hash_map::Entry;
+
+        match self.0.entry(Value::Usize(index)) {
+            Entry::Occupied(_) => {},
+            Entry::Vacant(x) => {x.insert(Value::Null);}
+        };
+        self.0.get_mut(&Value::Usize(index)).unwrap()
+    }
+}
+
+impl<'b> IndexMut<&'b str> for Table {
+    fn index_mut(&mut self, index: &'b str) -> &mut Value {
+        self.0.entry(Value::str(index)).or_insert(Value::Null)
+    }
+}
+
+impl Default for Table {
+    fn default() -> Table {
+        Table::new()
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for Table
+    where K: Into<Value>, V: Into<Value>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Table {
+        let mut table = Table::new();
+        table.extend(iter);
+        table
+    }
+}
+
+impl<K, V> Extend<(K, V)> for Table
+    where K: Into<Value>, V: Into<Value>
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0.extend(iter.into_iter().map(|(key, val)| (key.into(), val.into())));
+    }
+}
+
+impl IntoIterator for Table {
+    type Item = (Value, Value);
+    type IntoIter = hash_map::IntoIter<Value, Value>;
+
+    fn into_iter(self) -> hash_map::IntoIter<Value, Value> {
+        self.0.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Table {
+    type Item = (&'a Value, &'a Value);
+    type IntoIter = hash_map::Iter<'a, Value, Value>;
+
+    fn into_iter(self) -> hash_map::Iter<'a, Value, Value> {
+        self.0.iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a mut Table {
+    type Item = (&'a Value, &'a mut Value);
+    type IntoIter = hash_map::IterMut<'a, Value, Value>;
+
+    fn into_iter(self) -> hash_map::IterMut<'a, Value, Value> {
+        self.0.iter_mut()
+    }
+}
+
+impl Table {
+    /// Creates new table.
+    pub fn new() -> Table {
+        Table(HashMap::new())
+    }
+
+    /// Creates new table with capacity.
+    pub fn with_capacity(capacity: usize) -> Table {
+        Table(HashMap::with_capacity(capacity))
+    }
+
+    /// Returns the number of entries the table can hold without
+    /// reallocating.
+    pub fn capacity(&self) -> usize {
+        self.0.capacity()
+    }
+
+    /// Shrinks the capacity as much as possible.
+    ///
+    /// Removing entries never releases memory by itself, so call this
+    /// after a table that lives long has shed many entries.
+    pub fn shrink_to_fit(&mut self) {
+        self.0.shrink_to_fit()
+    }
+
+    /// Reserves capacity for at least `additional` more entries.
+    ///
+    /// Loaders that know how many entries will follow can avoid growing
+    /// the table several times.
+    pub fn reserve(&mut self, additional: usize) {
+        self.0.reserve(additional)
+    }
+
+    /// Removes all entries, keeping the capacity for reuse.
+    pub fn clear(&mut self) {
+        self.0.clear()
+    }
+
+    /// Removes all entries and releases the memory they used.
+    ///
+    /// A table that is refilled with batches of varying size keeps the
+    /// capacity of its largest batch when cleared with `clear`. This
+    /// instead lets the capacity start over, so memory does not ratchet up.
+    pub fn clear_and_shrink(&mut self) {
+        self.0 = HashMap::new();
+    }
+
+    /// Borrows the hash map that stores the entries.
+    pub fn as_map(&self) -> &HashMap<Value, Value> {
+        &self.0
+    }
+
+    /// Mutably borrows the hash map that stores the entries.
+    pub fn as_map_mut(&mut self) -> &mut HashMap<Value, Value> {
+        &mut self.0
+    }
+
+    /// Unwraps the hash map that stores the entries.
+    pub fn into_inner(self) -> HashMap<Value, Value> {
+        self.0
+    }
+
+    /// Returns the value stored under a key, or `None` if missing.
+    pub fn get_value(&self, key: &Value) -> Option<&Value> {
+        self.0.get(key)
+    }
+
+    /// Returns the value stored under an integer key, or `None` if missing.
+    pub fn get_usize(&self, key: usize) -> Option<&Value> {
+        self.0.get(&Value::Usize(key))
+    }
+
+    /// Returns the value stored under a string key, or `None` if missing.
+    ///
+    /// Only `Value::String` keys are matched. The key is looked up as it
+    /// is, without building a `Value`, so this never allocates.
+    pub fn get_str(&self, key: &str) -> Option<&Value> {
+        self.0.get(&key as &dyn key::Key)
+    }
+
+    /// Returns the value stored under a key, or `default` if missing.
+    pub fn get_or<'a>(&'a self, key: &Value, default: &'a Value) -> &'a Value {
+        self.0.get(key).unwrap_or(default)
+    }
+
+    /// Returns the string under a string key, or `default` if the key is
+    /// missing or does not hold a string.
+    pub fn get_str_or<'a>(&'a self, key: &str, default: &'a str) -> &'a str {
+        self.get_str(key).and_then(Value::as_str).unwrap_or(default)
+    }
+
+    /// Returns the bool under a string key, or `default` if the key is
+    /// missing or does not hold a bool.
+    pub fn get_bool_or(&self, key: &str, default: bool) -> bool {
+        self.get_str(key).and_then(Value::as_bool).unwrap_or(default)
+    }
+
+    /// Returns the number under a string key as `f64`, or `default` if the
+    /// key is missing or does not hold a number.
+    ///
+    /// Integers are accepted too and converted like in `Value::as_number`.
+    pub fn get_f64_or(&self, key: &str, default: f64) -> f64 {
+        self.get_str(key).and_then(Value::as_number).unwrap_or(default)
+    }
+
+    /// Returns the integer under a string key as `usize`, or `default` if
+    /// the key is missing or does not hold an integer that fits.
+    ///
+    /// Any integer variant is accepted, but floats are not.
+    pub fn get_usize_or(&self, key: &str, default: usize) -> usize {
+        self.get_integer(key).unwrap_or(default)
+    }
+
+    /// Works like `get_usize_or`, but for `u64`.
+    pub fn get_u64_or(&self, key: &str, default: u64) -> u64 {
+        self.get_integer(key).unwrap_or(default)
+    }
+
+    /// Works like `get_usize_or`, but for `i64`.
+    pub fn get_i64_or(&self, key: &str, default: i64) -> i64 {
+        self.get_integer(key).unwrap_or(default)
+    }
+
+    fn get_integer<T: TryFrom<i128>>(&self, key: &str) -> Option<T> {
+        let x = self.get_str(key)?.as_integer()?;
+        T::try_from(x).ok()
+    }
+
+    /// Inserts all entries of another table, overwriting on collision.
+    pub fn merge(&mut self, other: Table) {
+        self.0.extend(other.0);
+    }
+
+    /// Merges another table, recursing where both sides hold a table.
+    ///
+    /// Other values are overwritten like in `merge`. Shared nested
+    /// tables are cloned before they are changed.
+    pub fn merge_deep(&mut self, other: Table) {
+        for (key, val) in other.0 {
+            if let Value::Table(theirs) = val {
+                if let Some(&mut Value::Table(ref mut ours)) = self.0.get_mut(&key) {
+                    let theirs = Arc::try_unwrap(theirs)
+                        .unwrap_or_else(|theirs| (*theirs).clone());
+                    Arc::make_mut(ours).merge_deep(theirs);
+                    continue;
+                }
+                self.0.insert(key, Value::Table(theirs));
+            } else {
+                self.0.insert(key, val);
+            }
+        }
+    }
+
+    /// Applies a JSON merge patch as defined by RFC 7386.
+    ///
+    /// Entries of a table patch overwrite entries of this table, except
+    /// that `Null` removes the entry and a nested table is applied as a
+    /// patch itself. Only `Value::Array` counts as an array, which replaces
+    /// the entry wholesale, and `from_json_str` reads JSON arrays as such.
+    /// Tables keyed by indices are merged like other tables. A patch that
+    /// is not a table would replace the whole document and is ignored
+    /// here, see `Value::merge_patch`.
+    pub fn merge_patch(&mut self, patch: &Value) {
+        if let Value::Table(ref patch) = *patch {
+            for (key, val) in patch.iter() {
+                if val.is_null() {
+                    self.0.remove(key);
+                } else if val.is_table() {
+                    self.0.entry(key.clone()).or_insert(Value::Null).merge_patch(val);
+                } else {
+                    self.0.insert(key.clone(), val.clone());
+                }
+            }
+        }
+    }
+
+    /// Clones the table without sharing any data with the original.
+    ///
+    /// See `Value::deep_clone`.
+    pub fn deep_clone(&self) -> Table {
+        self.0.iter().map(|(key, val)| (key.deep_clone(), val.deep_clone())).collect()
+    }
+
+    /// Returns a table with the same keys and the values mapped by a closure.
+    pub fn map_values<F>(&self, mut f: F) -> Table
+        where F: FnMut(&Value) -> Value
+    {
+        let mut out = Table::with_capacity(self.len());
+        for (key, val) in self.0.iter() {
+            out.0.insert(key.clone(), f(val));
+        }
+        out
+    }
+
+    /// Returns a table with the entries for which a closure returns `true`.
+    pub fn filter<F>(&self, mut f: F) -> Table
+        where F: FnMut(&Value, &Value) -> bool
+    {
+        self.0.iter()
+            .filter(|&(key, val)| f(key, val))
+            .map(|(key, val)| (key.clone(), val.clone()))
+            .collect()
+    }
+
+    /// Keeps only the entries for which a closure returns `true`.
+    ///
+    /// This works like `HashMap::retain`, but changes the table in place.
+    pub fn retain<F>(&mut self, f: F)
+        where F: FnMut(&Value, &mut Value) -> bool
+    {
+        self.0.retain(f)
+    }
+
+    /// Splits the table into the entries for which a closure returns
+    /// `true` and the rest.
+    ///
+    /// The entries are moved, not cloned.
+    pub fn partition<F>(self, mut f: F) -> (Table, Table)
+        where F: FnMut(&Value, &Value) -> bool
+    {
+        let (yes, no) = self.0.into_iter().partition(|&(ref key, ref val)| f(key, val));
+        (Table(yes), Table(no))
+    }
+
+    /// Counts the values of each kind in this table.
+    ///
+    /// Nested tables and arrays count as one value each. Kinds that do not
+    /// occur are left out.
+    pub fn kind_histogram(&self) -> HashMap<ValueKind, usize> {
+        let mut counts = HashMap::new();
+        for val in self.0.values() {
+            *counts.entry(val.kind()).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Counts the values of each kind, recursing into nested tables and
+    /// arrays.
+    ///
+    /// Nested tables and arrays are counted themselves and then their
+    /// values are counted too. Keys are not counted.
+    pub fn deep_kind_histogram(&self) -> HashMap<ValueKind, usize> {
+        fn count(val: &Value, counts: &mut HashMap<ValueKind, usize>) {
+            *counts.entry(val.kind()).or_insert(0) += 1;
+            match *val {
+                Value::Array(ref items) => {
+                    for item in items.iter() {
+                        count(item, counts);
+                    }
+                }
+                Value::Table(ref table) => {
+                    for val in table.0.values() {
+                        count(val, counts);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let mut counts = HashMap::new();
+        for val in self.0.values() {
+            count(val, &mut counts);
+        }
+        counts
+    }
+
+    /// Moves the value under one key to another key.
+    ///
+    /// Any value under the new key is overwritten. Returns `false` and
+    /// leaves the table unchanged if the old key is missing.
+    pub fn rename_key(&mut self, from: &Value, to: Value) -> bool {
+        match self.0.remove(from) {
+            Some(val) => {
+                self.0.insert(to, val);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns a table with keys and values swapped.
+    ///
+    /// When several keys hold equal values, the last key in sorted order
+    /// wins, so the result does not depend on the order of entries.
+    pub fn invert(&self) -> Table {
+        let mut out = Table::with_capacity(self.len());
+        for (key, val) in self.sorted_entries() {
+            out.0.insert(val.clone(), key.clone());
+        }
+        out
+    }
+
+    /// Partitions the entries into sub-tables keyed by a computed group.
+    ///
+    /// The closure is called with each key and value, and the entry is
+    /// copied into the sub-table under the returned group. The result is
+    /// deterministic: entries are visited in key order, so when groups that
+    /// compare equal have different representations, like `Usize(1)` and
+    /// `F64(1.0)`, the one computed for the smallest key is kept.
+    pub fn group_by<F>(&self, mut f: F) -> Table
+        where F: FnMut(&Value, &Value) -> Value
+    {
+        let mut out = Table::new();
+        for (key, val) in self.sorted_entries() {
+            out.entry_table(f(key, val)).0.insert(key.clone(), val.clone());
+        }
+        out
+    }
+
+    /// Returns the table with all keys and values canonicalized.
+    ///
+    /// See `Value::canonicalize`.
+    pub fn canonicalize(&self) -> Table {
+        self.0.iter().map(|(key, val)| (key.canonicalize(), val.canonicalize())).collect()
+    }
+
+    /// Compares tables, treating a `Null` entry the same as a missing key.
+    ///
+    /// Nested tables are compared the same way, also when they are
+    /// elements of arrays. Array elements themselves are never ignored,
+    /// so `[null]` and `[]` differ. Otherwise values compare with `==`.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::Value;
+    ///
+    /// # fn main() {
+    /// let a = table!{ "name" => "Sven", "pos" => table!{ "x" => 1.0 } };
+    /// let b = table!{
+    ///     "name" => "Sven",
+    ///     "pos" => table!{ "x" => 1.0, "y" => Value::Null },
+    /// };
+    /// assert!(a != b);
+    /// assert!(a.eq_ignoring_nulls(&b));
+    /// # }
+    /// ```
+    pub fn eq_ignoring_nulls(&self, other: &Table) -> bool {
+        self.0.iter().all(|(key, val)| match other.0.get(key) {
+            Some(other) => eq_ignoring_nulls(val, other),
+            None => val.is_null(),
+        }) && other.0.iter().all(|(key, val)| val.is_null() || self.0.contains_key(key))
+    }
+
+    /// Estimates the heap memory owned by the table, in bytes.
+    ///
+    /// The hash map is counted as one key, one value and one control byte
+    /// per slot of capacity, which approximates the standard library's
+    /// layout. Keys and values add their own heap memory as counted by
+    /// `Value::memory_usage`, so shared data is counted once per reference.
+    pub fn memory_usage(&self) -> usize {
+        use std::mem::size_of;
+
+        let slot = 2 * size_of::<Value>() + 1;
+        self.0.capacity() * slot + self.0.iter()
+            .map(|(key, val)| key.memory_usage() + val.memory_usage())
+            .sum::<usize>()
+    }
+
+    /// Mutably borrows the value under a key, inserting a default first
+    /// if the key is missing.
+    ///
+    /// Unlike `IndexMut`, which inserts `Null`, this lets the missing value
+    /// start out as something meaningful.
+    pub fn entry_or_insert<K>(&mut self, key: K, default: Value) -> &mut Value
+        where K: Into<Value>
+    {
+        self.0.entry(key.into()).or_insert(default)
+    }
+
+    /// Mutably borrows the nested table under a key.
+    ///
+    /// An empty table is inserted if the key is missing or holds a value
+    /// that is not a table. A shared table is cloned before it is returned.
+    pub fn entry_table<K>(&mut self, key: K) -> &mut Table
+        where K: Into<Value>
+    {
+        let val = self.0.entry(key.into()).or_insert(Value::Null);
+        if !val.is_table() {
+            *val = Value::table(Table::new());
+        }
+        val.as_table_mut().unwrap()
+    }
+
+    /// Mutably borrows the elements of the nested array under a key.
+    ///
+    /// An empty array is inserted if the key is missing or holds a value
+    /// that is not an array. A shared array is cloned before it is returned.
+    pub fn entry_array<K>(&mut self, key: K) -> &mut Vec<Value>
+        where K: Into<Value>
+    {
+        let val = self.0.entry(key.into()).or_insert(Value::Null);
+        if !val.is_array() {
+            *val = Value::from(Vec::<Value>::new());
+        }
+        val.as_array_mut().unwrap()
+    }
+
+    /// Inserts a value after the highest index key and returns its index.
+    ///
+    /// Index keys are whole non-negative numbers. If the keys have gaps,
+    /// the value goes after the highest one instead of filling a gap.
+    /// Returns `None` without inserting if the highest index is
+    /// `usize::MAX`.
+    ///
+    /// Finding the highest index looks at every key, so pushing `n` values
+    /// one at a time takes O(n²) time. To append many values, collect them
+    /// into a `Value::Array` or insert them under their indices instead.
+    pub fn push(&mut self, val: Value) -> Option<usize> {
+        let index = match self.last_index() {
+            Some(last) => last.checked_add(1)?,
+            None => 0,
+        };
+        self.0.insert(Value::Usize(index), val);
+        Some(index)
+    }
+
+    /// Removes and returns the value under the highest index key.
+    ///
+    /// Returns `None` if there is no index key. Gaps are skipped over, so
+    /// popping does not stop at the first missing index.
+    pub fn pop(&mut self) -> Option<Value> {
+        let index = self.last_index()?;
+        self.0.remove(&Value::Usize(index))
+    }
+
+    /// Returns `true` if the keys are exactly the indices `0..len`.
+    ///
+    /// An empty table counts as an empty array.
+    pub fn is_array(&self) -> bool {
+        (0..self.len()).all(|i| self.0.contains_key(&Value::Usize(i)))
+    }
+
+    /// Returns the values in index order, if the table is an array.
+    ///
+    /// Returns `None` for tables with gaps or non-index keys, see `is_array`.
+    pub fn to_vec(&self) -> Option<Vec<Value>> {
+        if !self.is_array() {
+            return None;
+        }
+        Some((0..self.len()).map(|i| self[i].clone()).collect())
+    }
+
+    /// Converts copies of the values to a concrete type.
+    ///
+    /// The values come in index order if the table is an array, see
+    /// `is_array`, and in arbitrary order otherwise. The conversion uses
+    /// the `TryFrom<Value>` implementations, which require the exact kind,
+    /// and stops at the first value that does not convert.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// # fn main() {
+    /// let vec3 = table!{ 0usize => 1.0, 1usize => 2.0, 2usize => 3.0 };
+    /// assert_eq!(vec3.values_as::<f64>(), Ok(vec![1.0, 2.0, 3.0]));
+    /// # }
+    /// ```
+    pub fn values_as<T>(&self) -> Result<Vec<T>, ValueError>
+        where T: TryFrom<Value, Error = ValueError>
+    {
+        if self.is_array() {
+            (0..self.len()).map(|i| T::try_from(self[i].clone())).collect()
+        } else {
+            self.0.values().map(|val| T::try_from(val.clone())).collect()
+        }
+    }
+
+    /// Returns copies of the entries sorted by key.
+    ///
+    /// Keys are ordered like `Ord for Value`, so tables with equal entries
+    /// give the same vector no matter in which order they were built. This
+    /// suits stable output, diffing and binary search by key.
+    pub fn to_sorted_vec(&self) -> Vec<(Value, Value)> {
+        let mut entries: Vec<(Value, Value)> = self.0.iter()
+            .map(|(key, val)| (key.clone(), val.clone()))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries
+    }
+
+    /// Returns copies of the entries in an ordered map.
+    ///
+    /// Keys are ordered like `Ord for Value`, which allows range queries
+    /// and iteration in a deterministic order. Nested tables stay shared.
+    pub fn to_btree(&self) -> BTreeMap<Value, Value> {
+        self.0.iter().map(|(key, val)| (key.clone(), val.clone())).collect()
+    }
+
+    /// Creates a table from the entries of an ordered map.
+    pub fn from_btree(map: BTreeMap<Value, Value>) -> Table {
+        Table(map.into_iter().collect())
+    }
+
+    fn last_index(&self) -> Option<usize> {
+        self.0.keys().filter_map(|key| key.to_index()).max()
+    }
+}
+
+#[cfg(test)]
 mod tests {
     extern crate test;
 
-    use super::*;
-    use self::test::Bencher;
-    use std::sync::Arc;
+    use super::*;
+    use self::test::Bencher;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_size() {
+        use std::mem::size_of;
+
+        // Make sure `Value` is not exceeding 128 bits on 64 bit systems.
+        // This is for performance reasons.
+        if size_of::<usize>() == 8 {
+            assert_eq!(size_of::<Value>(), 16);
+        }
+    }
+
+    #[test]
+    fn test_sync() {
+        fn foo<T: Sync>() {}
+
+        // Make sure `Value` can be shared among tasks.
+        foo::<Value>();
+        foo::<Table>();
+    }
+
+    #[test]
+    fn test_vec3() {
+        let mut vec3 = Table::with_capacity(3);
+        vec3[0] = Value::f64(1.0);
+        vec3[1] = Value::f64(2.0);
+        vec3[2] = Value::f64(3.0);
+        assert_eq!(vec3[0], Value::f64(1.0));
+        assert_eq!(vec3[1], Value::f64(2.0));
+        assert_eq!(vec3[2], Value::f64(3.0));
+        vec3.clear();
+        assert_eq!(vec3.len(), 0);
+    }
+
+    #[test]
+    fn test_table_as_key() {
+        use std::borrow::ToOwned;
+
+        let a = Value::Table(Arc::new(Table::new()));
+        let mut b = Table::new();
+        b[a] = Value::String(Arc::new("hello".to_owned()).into());
+    }
+
+    #[test]
+    fn test_str_as_key() {
+        use std::borrow::ToOwned;
+
+        let mut a = Table::new();
+        a.insert(Value::String(Arc::new("hello".to_owned()).into()),
+            Value::String(Arc::new("world".to_owned()).into()));
+        assert!(
+            if let Some(&Value::String(_)) = a.get_str("hello") { true }
+            else { false }
+        );
+        assert_eq!(a["hello"], Value::String(Arc::new("world".to_owned()).into()));
+        a["hello2"] = Value::String(Arc::new("world".to_owned()).into());
+        assert_eq!(a["hello"], a["hello2"]);
+    }
+
+    #[test]
+    fn test_get() {
+        let mut a = Table::new();
+        a[0] = Value::Bool(true);
+        a["x"] = Value::Bool(false);
+        assert_eq!(a.get_usize(0), Some(&Value::Bool(true)));
+        assert_eq!(a.get_usize(1), None);
+        assert_eq!(a.get_str("x"), Some(&Value::Bool(false)));
+        assert_eq!(a.get_str("y"), None);
+        a["a key longer than inline"] = Value::Usize(2);
+        a[Value::Char('c')] = Value::Usize(3);
+        assert_eq!(a.get_str("a key longer than inline"), Some(&Value::Usize(2)));
+        assert_eq!(a.get_str("c"), None);
+        assert_eq!(a.get_value(&Value::Usize(0)), Some(&Value::Bool(true)));
+        assert_eq!(a.get_value(&Value::Null), None);
+    }
+
+    #[test]
+    fn test_str_key_does_not_alias() {
+        let mut a = Table::new();
+        a[Value::Null] = Value::Bool(true);
+        a[Value::Bool(false)] = Value::Bool(true);
+        assert_eq!(a.get_str(""), None);
+        a[""] = Value::Usize(1);
+        assert_eq!(a.len(), 3);
+        assert_eq!(a[""], Value::Usize(1));
+        assert_eq!(a[Value::Null], Value::Bool(true));
+    }
+
+    #[test]
+    fn test_conversion() {
+        let _: Value = (0 as usize).into();
+        let _: Value = (0 as u64).into();
+        let _: Value = (0 as i64).into();
+        let _: Value = 0.0.into();
+        let _: Value = "hello".into();
+        let _: Value = "hello".to_owned().into();
+        let _: Value = Table::new().into();
+        let _: Value = vec![Value::Null].into();
+    }
+
+    #[test]
+    fn test_widening_conversion() {
+        assert_eq!(Value::from(true), Value::Bool(true));
+        assert_eq!(Value::from(0.5f32), Value::f64(0.5));
+        assert_eq!(Value::from(u8::MAX).kind(), ValueKind::U64);
+        assert_eq!(Value::from(u32::MAX), Value::U64(u32::MAX as u64));
+        assert_eq!(Value::from(i8::MIN), Value::I64(-128));
+        assert_eq!(Value::from(-7i16).kind(), ValueKind::I64);
+        assert_eq!(Value::from(i32::MIN), Value::I64(i32::MIN as i64));
+
+        let a = table!{ 0 => 1.0, 1 => 2.0 };
+        assert_eq!(a[1], Value::f64(2.0));
+    }
+
+    #[test]
+    fn test_array() {
+        let mut a = Value::array(vec![Value::Usize(1), Value::str("two")]);
+        assert!(a.is_array());
+        assert_eq!(a.kind(), ValueKind::Array);
+        assert_eq!(a.as_array().unwrap().len(), 2);
+        let b = a.clone();
+        a.as_array_mut().unwrap().push(Value::Null);
+        assert_eq!(a.as_array().unwrap().len(), 3);
+        assert_eq!(b.as_array().unwrap().len(), 2);
+        assert!(a != b);
+        assert_eq!(b, Value::from(vec![Value::I64(1), Value::str("two")]));
+        assert_eq!(Value::Null.as_array(), None);
+
+        let mut t = Table::new();
+        t[b.clone()] = Value::Bool(true);
+        assert_eq!(t[b.clone()], Value::Bool(true));
+        assert_eq!(b.to_string(), "[1, \"two\"]");
+        assert_eq!(format!("{:#}", b), "[\n    1,\n    \"two\",\n]");
+        assert_eq!(b.to_json_string(), "[1,\"two\"]");
+        assert!(Value::str("z") < b && b < Value::table(Table::new()));
+    }
+
+    #[test]
+    fn test_try_from() {
+        use std::convert::TryFrom;
+
+        assert_eq!(f64::try_from(Value::f64(1.5)), Ok(1.5));
+        assert_eq!(i64::try_from(Value::I64(-3)), Ok(-3));
+        assert_eq!(u64::try_from(&Value::U64(3)), Ok(3));
+        assert_eq!(usize::try_from(&Value::Usize(3)), Ok(3));
+        assert_eq!(i64::try_from(Value::U64(3)),
+            Err(ValueError { expected: ValueKind::I64, found: ValueKind::U64 }));
+        let err = f64::try_from(&Value::str("1.0")).unwrap_err();
+        assert_eq!(err.to_string(), "expected f64, found string");
+    }
+
+    #[test]
+    fn test_try_from_string_and_bool() {
+        use std::convert::TryFrom;
+
+        assert_eq!(bool::try_from(Value::Bool(true)), Ok(true));
+        assert_eq!(bool::try_from(&Value::Null),
+            Err(ValueError { expected: ValueKind::Bool, found: ValueKind::Null }));
+        assert_eq!(String::try_from(Value::str("hello")), Ok("hello".to_owned()));
+        let shared = Value::str("world");
+        assert_eq!(String::try_from(shared.clone()), Ok("world".to_owned()));
+        assert_eq!(String::try_from(&shared), Ok("world".to_owned()));
+        assert_eq!(String::try_from(Value::Usize(1)),
+            Err(ValueError { expected: ValueKind::String, found: ValueKind::Usize }));
+    }
+
+    #[test]
+    fn test_accessors() {
+        let mut a = Table::new();
+        a["name"] = Value::str("table");
+        let a = Value::table(a);
+        assert_eq!(a.as_table().unwrap()["name"].as_str(), Some("table"));
+        assert_eq!(a.as_str(), None);
+        assert_eq!(Value::f64(0.5).as_f64(), Some(0.5));
+        assert_eq!(Value::Bool(true).as_bool(), Some(true));
+        assert_eq!(Value::Usize(1).as_usize(), Some(1));
+        assert_eq!(Value::U64(2).as_u64(), Some(2));
+        assert_eq!(Value::I64(-3).as_i64(), Some(-3));
+        assert_eq!(Value::I64(-3).as_u64(), None);
+        assert_eq!(Value::Null.as_bool(), None);
+    }
+
+    #[test]
+    fn test_predicates() {
+        assert!(Value::Null.is_null());
+        assert!(Value::Bool(false).is_bool());
+        assert!(Value::Usize(0).is_number());
+        assert!(Value::U64(0).is_number());
+        assert!(Value::I64(0).is_number());
+        assert!(Value::f64(0.0).is_number());
+        assert!(!Value::str("0").is_number());
+        assert!(Value::str("0").is_string());
+        assert!(Value::table(Table::new()).is_table());
+        assert!(!Value::Null.is_table());
+    }
+
+    #[test]
+    fn test_kind() {
+        assert_eq!(Value::Null.kind(), ValueKind::Null);
+        assert_eq!(Value::Usize(0).kind(), ValueKind::Usize);
+        assert_eq!(Value::f64(0.0).kind(), ValueKind::F64);
+        assert_eq!(Value::table(Table::new()).kind(), ValueKind::Table);
+        assert_eq!(ValueKind::Table.to_string(), "table");
+        assert_eq!(Value::Null.type_name(), "null");
+        assert_eq!(Value::I64(-1).type_name(), "i64");
+        assert_eq!(Value::str("x").type_name(), "string");
+        assert_eq!(Value::table(Table::new()).type_name(), "table");
+        assert_eq!(ValueKind::Bytes.name(), "bytes");
+        const NAME: &str = Value::Bool(true).type_name();
+        assert_eq!(NAME, "bool");
+        assert_eq!(ValueError { expected: ValueKind::Table, found: ValueKind::String }
+            .to_string(), "expected table, found string");
+    }
+
+    #[test]
+    fn test_f64_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash(val: f64) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            F64(val).hash(&mut hasher);
+            hasher.finish()
+        }
+
+        assert!(hash(1.1) != hash(1.9));
+        assert!(hash(1.0) != hash(1.5));
+        assert!(hash(-1.0) != hash(-2.0));
+        assert_eq!(hash(0.0), hash(-0.0));
+
+        let mut a = Table::new();
+        a[Value::f64(1.1)] = Value::Usize(1);
+        a[Value::f64(1.9)] = Value::Usize(2);
+        a[Value::f64(-0.0)] = Value::Usize(3);
+        assert_eq!(a[Value::f64(1.1)], Value::Usize(1));
+        assert_eq!(a[Value::f64(1.9)], Value::Usize(2));
+        assert_eq!(a[Value::f64(0.0)], Value::Usize(3));
+    }
+
+    #[test]
+    fn test_f64_nan_key() {
+        assert_eq!(F64(f64::NAN), F64(f64::NAN));
+        assert_eq!(F64(f64::NAN), F64(-f64::NAN));
+        assert_eq!(F64(0.0), F64(-0.0));
+        assert!(F64(f64::NAN) != F64(0.0));
+
+        let mut a = Table::new();
+        a[Value::f64(f64::NAN)] = Value::str("nan");
+        a[Value::f64(-f64::NAN)] = Value::str("also nan");
+        assert_eq!(a.len(), 1);
+        assert_eq!(a.get_value(&Value::f64(f64::NAN)), Some(&Value::str("also nan")));
+    }
+
+    #[test]
+    fn test_f64_ord() {
+        let mut a = vec![F64(f64::NAN), F64(1.0), F64(f64::INFINITY), F64(-0.0),
+            F64(f64::NEG_INFINITY), F64(-2.5)];
+        a.sort();
+        assert_eq!(a, vec![F64(f64::NEG_INFINITY), F64(-2.5), F64(0.0), F64(1.0),
+            F64(f64::INFINITY), F64(f64::NAN)]);
+        assert_eq!(F64(-0.0).cmp(&F64(0.0)), Ordering::Equal);
+        assert_eq!(F64(-f64::NAN).cmp(&F64(f64::NAN)), Ordering::Equal);
+    }
+
+    #[test]
+    fn test_value_ord() {
+        let mut inner = Table::new();
+        inner[0] = Value::Bool(true);
+        let inner = Value::table(inner);
+        let mut a = vec![
+            inner.clone(),
+            Value::str("b"),
+            Value::f64(f64::NAN),
+            Value::I64(-1),
+            Value::str("a"),
+            Value::f64(2.5),
+            Value::Usize(2),
+            Value::U64(u64::MAX),
+            Value::table(Table::new()),
+            Value::Bool(true),
+            Value::Null,
+            Value::Bool(false),
+        ];
+        a.sort();
+        assert_eq!(a, vec![
+            Value::Null,
+            Value::Bool(false),
+            Value::Bool(true),
+            Value::I64(-1),
+            Value::Usize(2),
+            Value::f64(2.5),
+            Value::U64(u64::MAX),
+            Value::f64(f64::NAN),
+            Value::str("a"),
+            Value::str("b"),
+            Value::table(Table::new()),
+            inner,
+        ]);
+        assert_eq!(Value::Usize(5).cmp(&Value::I64(5)), Ordering::Equal);
+        assert_eq!(Value::f64(5.0).cmp(&Value::U64(5)), Ordering::Equal);
+        assert_eq!(Value::f64(-0.5).cmp(&Value::I64(0)), Ordering::Less);
+        assert_eq!(Value::f64(-1.5).cmp(&Value::I64(-1)), Ordering::Less);
+        assert_eq!(Value::f64(1e30).cmp(&Value::U64(u64::MAX)), Ordering::Greater);
+    }
+
+    #[test]
+    fn test_numeric_keys() {
+        assert_eq!(Value::Usize(5), Value::I64(5));
+        assert_eq!(Value::U64(5), Value::f64(5.0));
+        assert!(Value::I64(-1) != Value::U64(u64::MAX));
+        assert!(Value::f64(5.5) != Value::I64(5));
+        assert!(Value::f64(f64::NAN) != Value::I64(0));
+
+        let mut a = Table::new();
+        a[Value::Usize(1)] = Value::str("one");
+        a[Value::U64(u64::MAX)] = Value::str("max");
+        a[Value::f64(0.5)] = Value::str("half");
+        assert_eq!(a[Value::I64(1)], Value::str("one"));
+        assert_eq!(a[Value::f64(1.0)], Value::str("one"));
+        assert_eq!(a.get_value(&Value::f64(u64::MAX as f64)), None);
+        assert_eq!(Value::U64(1 << 53), Value::f64(9007199254740992.0));
+        assert_eq!(a.get_value(&Value::I64(-1)), None);
+        assert_eq!(a[Value::f64(0.5)], Value::str("half"));
+        a[Value::I64(1)] = Value::str("uno");
+        assert_eq!(a.len(), 3);
+        assert_eq!(a[1], Value::str("uno"));
+    }
 
     #[test]
-    fn test_size() {
-        use std::mem::size_of;
+    fn test_integer_key_variants() {
+        let mut a = Table::new();
+        a[0] = Value::str("zero");
+        assert_eq!(a[Value::U64(0)], Value::str("zero"));
+        assert_eq!(a.get_value(&Value::U64(0)), Some(&Value::str("zero")));
+        assert_eq!(a.get(&Value::I64(0)), Some(&Value::str("zero")));
+        let val = Value::table(a.clone());
+        assert_eq!(val.get(&Value::U64(0)), Some(&Value::str("zero")));
+        a[Value::U64(0)] = Value::str("nil");
+        assert_eq!(a.len(), 1);
+        assert_eq!(a[0], Value::str("nil"));
+        assert_eq!(a.remove(&Value::I64(0)), Some(Value::str("nil")));
+    }
 
-        // Make sure `Value` is not exceeding 128 bits on 64 bit systems.
-        // This is for performance reasons.
-        if size_of::<usize>() == 8 {
-            assert_eq!(size_of::<Value>(), 16);
+    #[test]
+    fn test_table_macro() {
+        let empty = table!{};
+        assert_eq!(empty.len(), 0);
+
+        let a = table!{
+            "name" => "Sven",
+            "pos" => table!{ 0usize => 1.0, 1usize => 2.0 },
+        };
+        assert_eq!(a["name"], Value::str("Sven"));
+        assert_eq!(a["pos"].as_table().unwrap()[1], Value::f64(2.0));
+        assert_eq!(table!{ "a" => 1usize }, table!{ "a" => 1usize, });
+    }
+
+    #[test]
+    fn test_display() {
+        let a = table!{
+            "name" => "Sven",
+            "pos" => table!{ 0usize => 1.5, 1usize => -2.0 },
+            "empty" => Table::new(),
+            3usize => Value::Null,
+        };
+        assert_eq!(Value::str("a b").to_string(), "a b");
+        assert_eq!(Value::f64(1.5).to_string(), "1.5");
+        assert_eq!(a.to_string(), "{ 3: null, \"empty\": {}, \"name\": \"Sven\", \
+            \"pos\": { 0: 1.5, 1: -2 } }");
+        assert_eq!(format!("{:#}", Value::table(a)), "{
+    3: null,
+    \"empty\": {},
+    \"name\": \"Sven\",
+    \"pos\": {
+        0: 1.5,
+        1: -2,
+    },
+}");
+    }
+
+    #[test]
+    fn test_merge() {
+        let defaults = table!{
+            "name" => "player",
+            "window" => table!{ "width" => 800usize, "height" => 600usize },
+        };
+        let overrides = table!{
+            "window" => table!{ "width" => 1024usize },
+            "vsync" => Value::Bool(true),
+        };
+
+        let mut shallow = defaults.clone();
+        shallow.merge(overrides.clone());
+        assert_eq!(shallow, table!{
+            "name" => "player",
+            "window" => table!{ "width" => 1024usize },
+            "vsync" => Value::Bool(true),
+        });
+
+        let mut deep = defaults.clone();
+        deep.merge_deep(overrides);
+        assert_eq!(deep, table!{
+            "name" => "player",
+            "window" => table!{ "width" => 1024usize, "height" => 600usize },
+            "vsync" => Value::Bool(true),
+        });
+        assert_eq!(defaults["window"].as_table().unwrap()["width"],
+            Value::Usize(800));
+    }
+
+    #[test]
+    fn test_from_iter() {
+        let a: Table = vec![("a", 1usize), ("b", 2usize)].into_iter().collect();
+        assert_eq!(a, table!{ "a" => 1usize, "b" => 2usize });
+
+        let mut b: Table = vec![(Value::Null, Value::Bool(true))].into_iter().collect();
+        b.extend(vec![("c", "d")]);
+        b.extend(a.clone().0);
+        assert_eq!(b.len(), 4);
+        assert_eq!(b["c"], Value::str("d"));
+        assert_eq!(b["b"], Value::Usize(2));
+    }
+
+    #[test]
+    fn test_into_iter() {
+        let mut a = table!{ "a" => 1usize, "b" => 2usize };
+        for (_, val) in &mut a {
+            *val = Value::Usize(val.as_usize().unwrap() * 10);
         }
+        let mut sum = 0;
+        for (_, val) in &a {
+            sum += val.as_usize().unwrap();
+        }
+        assert_eq!(sum, 30);
+        let b: Table = a.clone().into_iter().collect();
+        assert_eq!(a, b);
     }
 
     #[test]
-    fn test_sync() {
-        fn foo<T: Sync>() {}
+    fn test_default() {
+        #[derive(Default)]
+        struct Config {
+            settings: Table,
+            fallback: Value,
+        }
 
-        // Make sure `Value` can be shared among tasks.
-        foo::<Value>();
-        foo::<Table>();
+        let config = Config::default();
+        assert!(config.settings.is_empty());
+        assert_eq!(config.fallback, Value::Null);
     }
 
     #[test]
-    fn test_vec3() {
-        let mut vec3 = Table::with_capacity(3);
-        vec3[0] = Value::f64(1.0);
-        vec3[1] = Value::f64(2.0);
-        vec3[2] = Value::f64(3.0);
-        assert_eq!(vec3[0], Value::f64(1.0));
-        assert_eq!(vec3[1], Value::f64(2.0));
-        assert_eq!(vec3[2], Value::f64(3.0));
-        vec3.clear();
-        assert_eq!(vec3.len(), 0);
+    fn test_len() {
+        assert_eq!(Value::str("").len(), Some(0));
+        assert_eq!(Value::str("h\u{e9}").len(), Some(3));
+        assert_eq!(Value::table(Table::new()).len(), Some(0));
+        assert_eq!(Value::table(table!{ "a" => 1usize }).len(), Some(1));
+        assert_eq!(Value::array(vec![Value::Null]).len(), Some(1));
+        assert_eq!(Value::Usize(3).len(), None);
+        assert_eq!(Value::str("").is_empty(), Some(true));
+        assert_eq!(Value::table(Table::new()).is_empty(), Some(true));
+        assert_eq!(Value::str("a").is_empty(), Some(false));
+        assert_eq!(Value::Null.is_empty(), None);
     }
 
     #[test]
-    fn test_table_as_key() {
-        use std::borrow::ToOwned;
+    fn test_eq_primitive() {
+        let a = table!{ "name" => "Sven", "level" => 3usize, "alive" => true };
+        assert_eq!(a["name"], "Sven");
+        assert_eq!("Sven", a["name"]);
+        assert_eq!(a["name"], *"Sven");
+        assert!(a["name"] != "Sv");
+        assert_eq!(a["level"], 3usize);
+        assert_eq!(a["level"], 3i64);
+        assert_eq!(a["level"], 3u64);
+        assert_eq!(a["level"], 3.0);
+        assert_eq!(3usize, a["level"]);
+        assert!(a["level"] != 4usize);
+        assert_eq!(a["alive"], true);
+        assert!(a["alive"] != 1usize);
+        assert!(Value::Usize(0) != false);
+        assert!(Value::str("3") != 3usize);
+    }
 
-        let a = Value::Table(Arc::new(Table::new()));
-        let mut b = Table::new();
-        b[a] = Value::String(Arc::new("hello".to_owned()));
+    #[test]
+    fn test_value_get() {
+        let a = Value::table(table!{
+            "list" => vec![Value::str("x"), Value::table(table!{ "y" => 1 })],
+            3 => "three",
+        });
+        assert_eq!(a.get("list").and_then(|v| v.get(1)).and_then(|v| v.get("y")),
+            Some(&Value::I64(1)));
+        assert_eq!(a.get(3), Some(&Value::str("three")));
+        assert_eq!(a.get(&Value::f64(3.0)), Some(&Value::str("three")));
+        let list = a.get("list").unwrap();
+        assert_eq!(list.get(&Value::I64(0)), Some(&Value::str("x")));
+        assert_eq!(list.get(&Value::f64(0.5)), None);
+        assert_eq!(list.get("0"), None);
+        assert_eq!(list.get(2), None);
+        assert_eq!(Value::Usize(1).get(0), None);
     }
 
     #[test]
-    fn test_str_as_key() {
-        use std::borrow::ToOwned;
+    fn test_merge_patch() {
+        fn json(text: &str) -> Value {
+            Value::from_json_str(text).unwrap()
+        }
+
+        fn patched(target: Value, patch: Value) -> Value {
+            let mut target = target;
+            target.merge_patch(&patch);
+            target
+        }
+
+        // The examples from RFC 7386, appendix A, as target, patch, result.
+        let cases = [
+            (r#"{"a":"b"}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"b":"c"}"#, r#"{"a":"b","b":"c"}"#),
+            (r#"{"a":"b"}"#, r#"{"a":null}"#, "{}"),
+            (r#"{"a":"b","b":"c"}"#, r#"{"a":null}"#, r#"{"b":"c"}"#),
+            (r#"{"a":["b"]}"#, r#"{"a":"c"}"#, r#"{"a":"c"}"#),
+            (r#"{"a":"c"}"#, r#"{"a":["b"]}"#, r#"{"a":["b"]}"#),
+            (r#"{"a":{"b":"c"}}"#, r#"{"a":{"b":"d","c":null}}"#, r#"{"a":{"b":"d"}}"#),
+            (r#"{"a":[{"b":"c"}]}"#, r#"{"a":[1]}"#, r#"{"a":[1]}"#),
+            (r#"["a","b"]"#, r#"["c","d"]"#, r#"["c","d"]"#),
+            (r#"{"a":"b"}"#, r#"["c"]"#, r#"["c"]"#),
+            (r#"{"a":"foo"}"#, "null", "null"),
+            (r#"{"a":"foo"}"#, r#""bar""#, r#""bar""#),
+            (r#"{"e":null}"#, r#"{"a":1}"#, r#"{"e":null,"a":1}"#),
+            (r#"[1,2]"#, r#"{"a":"b","c":null}"#, r#"{"a":"b"}"#),
+            ("{}", r#"{"a":{"bb":{"ccc":null}}}"#, r#"{"a":{"bb":{}}}"#),
+            // Arrays are replaced whole, not merged element by element.
+            (r#"{"a":[1,2,3]}"#, r#"{"a":[4]}"#, r#"{"a":[4]}"#),
+        ];
+        for &(target, patch, result) in &cases {
+            assert_eq!(patched(json(target), json(patch)), json(result), "{}", patch);
+        }
+
+        let mut a = table!{ "a" => "b" };
+        a.merge_patch(&Value::str("ignored"));
+        assert_eq!(a, table!{ "a" => "b" });
+    }
+
+    #[test]
+    fn test_approx_eq() {
+        let a = Value::table(table!{
+            "pos" => vec![Value::f64(0.1 + 0.2), Value::f64(1.0)],
+            "name" => "Sven",
+            "n" => 3,
+        });
+        let b = Value::table(table!{
+            "pos" => vec![Value::f64(0.3), Value::Usize(1)],
+            "name" => "Sven",
+            "n" => 3usize,
+        });
+        assert!(a != b);
+        assert!(a.approx_eq(&b, 1e-9));
+        assert!(!a.approx_eq(&b, 0.0));
+        assert!(Value::f64(1.0).approx_eq(&Value::f64(1.05), 0.1));
+        assert!(!Value::f64(1.0).approx_eq(&Value::f64(1.2), 0.1));
+        assert!(Value::f64(f64::NAN).approx_eq(&Value::f64(f64::NAN), 0.1));
+        assert!(!Value::I64(1).approx_eq(&Value::I64(2), 5.0));
+        assert!(!Value::str("1").approx_eq(&Value::f64(1.0), 0.1));
+
+        let missing = Value::table(table!{ "name" => "Sven", "n" => 3 });
+        assert!(!a.approx_eq(&missing, 1.0));
+        assert!(!missing.approx_eq(&a, 1.0));
+        let short = Value::array(vec![Value::f64(0.3)]);
+        assert!(!short.approx_eq(&Value::array(vec![Value::f64(0.3); 2]), 1.0));
+    }
+
+    #[test]
+    fn test_hash_table_order_independent() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash_of(table: &Table) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            table.hash(&mut hasher);
+            hasher.finish()
+        }
 
         let mut a = Table::new();
-        a.insert(Value::String(Arc::new("hello".to_owned())),
-            Value::String(Arc::new("world".to_owned())));
-        assert!(
-            if let Some(&Value::String(_)) = a.get("hello") { true }
-            else { false }
-        );
-        assert_eq!(a["hello"], Value::String(Arc::new("world".to_owned())));
-        a["hello2"] = Value::String(Arc::new("world".to_owned()));
-        assert_eq!(a["hello"], a["hello2"]);
+        let mut b = Table::with_capacity(64);
+        for i in 0..32 {
+            a.insert(Value::Usize(i), Value::str("x"));
+            b.insert(Value::Usize(31 - i), Value::str("x"));
+        }
+        let nested_a = table!{ "t" => a.clone() };
+        let nested_b = table!{ "t" => b.clone() };
+        assert_eq!(a, b);
+        assert_eq!(hash_of(&a), hash_of(&b));
+        assert_eq!(hash_of(&nested_a), hash_of(&nested_b));
+        assert!(hash_of(&a) != hash_of(&Table::new()));
     }
 
     #[test]
-    fn test_conversion() {
-        let _: Value = (0 as usize).into();
-        let _: Value = (0 as u64).into();
-        let _: Value = (0 as i64).into();
-        let _: Value = 0.0.into();
-        let _: Value = "hello".into();
-        let _: Value = "hello".to_owned().into();
-        let _: Value = Table::new().into();
+    fn test_array_helpers() {
+        let mut a = Table::new();
+        assert!(a.is_array());
+        assert_eq!(a.to_vec(), Some(vec![]));
+        assert_eq!(a.pop(), None);
+        assert_eq!(a.push(Value::str("a")), Some(0));
+        assert_eq!(a.push(Value::str("b")), Some(1));
+        assert!(a.is_array());
+        assert_eq!(a.to_vec(), Some(vec![Value::str("a"), Value::str("b")]));
+        assert_eq!(a.pop(), Some(Value::str("b")));
+        assert_eq!(a.len(), 1);
+
+        let mut gaps = table!{ 0 => 1, 5 => 2, "name" => "x" };
+        assert!(!gaps.is_array());
+        assert_eq!(gaps.to_vec(), None);
+        assert_eq!(gaps.push(Value::Usize(3)), Some(6));
+        assert_eq!(gaps.pop(), Some(Value::Usize(3)));
+        assert_eq!(gaps.pop(), Some(Value::Usize(2)));
+        assert_eq!(gaps.pop(), Some(Value::Usize(1)));
+        assert_eq!(gaps.pop(), None);
+        assert_eq!(gaps.len(), 1);
+
+        let mut full = table!{ usize::MAX => 1usize };
+        assert_eq!(full.push(Value::Usize(2)), None);
+        assert_eq!(full, table!{ usize::MAX => 1usize });
+    }
+
+    #[test]
+    fn test_entries() {
+        let inner = table!{ "x" => "a long string that is not inline" };
+        let a = Value::table(table!{ "inner" => inner.clone() });
+        let (key, val) = a.entries().unwrap().next().unwrap();
+        assert_eq!(key, &Value::str("inner"));
+        assert!(std::ptr::eq(val, a.get("inner").unwrap()));
+        assert_eq!(val.as_table(), Some(&inner));
+        let mut keys: Vec<&Value> = val.entries().unwrap().map(|(key, _)| key).collect();
+        keys.sort();
+        assert_eq!(keys, [&Value::str("x")]);
+        assert!(Value::Null.entries().is_none());
+        assert!(Value::array(vec![Value::Null]).entries().is_none());
+        assert_eq!(Value::table(Table::new()).entries().unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_as_table_mut() {
+        let inner = Value::table(table!{ "x" => 1 });
+        let mut a = inner.clone();
+        a.as_table_mut().unwrap().insert(Value::str("x"), Value::Usize(2));
+        assert_eq!(a.get("x"), Some(&Value::Usize(2)));
+        assert_eq!(inner.get("x"), Some(&Value::Usize(1)));
+        assert_eq!(Value::Null.as_table_mut(), None);
+
+        let mut b = table!{ "pos" => inner.clone(), "name" => "x" };
+        b.entry_table("pos").insert(Value::str("y"), Value::Usize(3));
+        b.entry_table("new").push(Value::Bool(true));
+        b.entry_table("name");
+        assert_eq!(b, table!{
+            "pos" => table!{ "x" => 1, "y" => 3 },
+            "new" => table!{ 0 => true },
+            "name" => Table::new(),
+        });
+        assert_eq!(inner.get("y"), None);
+    }
+
+    #[test]
+    fn test_entry_or_insert_and_array() {
+        let mut a = table!{ "hp" => 10, "tags" => vec![Value::str("a")], "x" => 1 };
+        *a.entry_or_insert("hp", Value::I64(0)) = Value::I64(9);
+        *a.entry_or_insert("mp", Value::I64(5)) = Value::I64(4);
+        let shared = a["tags"].clone();
+        a.entry_array("tags").push(Value::str("b"));
+        a.entry_array("new").push(Value::Usize(1));
+        a.entry_array("x");
+        assert_eq!(a, table!{
+            "hp" => 9,
+            "mp" => 4,
+            "tags" => vec![Value::str("a"), Value::str("b")],
+            "new" => vec![Value::Usize(1)],
+            "x" => Vec::<Value>::new(),
+        });
+        assert_eq!(shared, Value::from(vec![Value::str("a")]));
+    }
+
+    #[test]
+    fn test_to_sorted_vec() {
+        let entries = [
+            (Value::str("b"), Value::Usize(1)),
+            (Value::Null, Value::Usize(2)),
+            (Value::f64(2.5), Value::Usize(3)),
+            (Value::I64(-1), Value::Usize(4)),
+            (Value::str("a"), Value::Usize(5)),
+            (Value::Bool(true), Value::Usize(6)),
+        ];
+        let forward: Table = entries.iter().cloned().collect();
+        let backward: Table = entries.iter().rev().cloned().collect();
+        let sorted = forward.to_sorted_vec();
+        assert_eq!(sorted, backward.to_sorted_vec());
+        let keys: Vec<Value> = sorted.iter().map(|entry| entry.0.clone()).collect();
+        assert_eq!(keys, [Value::Null, Value::Bool(true), Value::I64(-1), Value::f64(2.5),
+            Value::str("a"), Value::str("b")]);
+        let found = sorted.binary_search_by(|entry| entry.0.cmp(&Value::str("a")));
+        assert_eq!(found, Ok(4));
+        assert_eq!(Table::new().to_sorted_vec(), vec![]);
+    }
+
+    #[test]
+    fn test_values_as() {
+        let vec3 = table!{ 2usize => 3.0, 0usize => 1.0, 1usize => 2.0 };
+        assert_eq!(vec3.values_as::<f64>(), Ok(vec![1.0, 2.0, 3.0]));
+        assert_eq!(vec3.values_as::<usize>(), Err(ValueError {
+            expected: ValueKind::Usize,
+            found: ValueKind::F64,
+        }));
+        let names = table!{ "a" => "x", "b" => "y" };
+        let mut names = names.values_as::<String>().unwrap();
+        names.sort();
+        assert_eq!(names, ["x", "y"]);
+        let mixed = table!{ 0usize => true, 1usize => 1.0 };
+        assert_eq!(mixed.values_as::<bool>().unwrap_err().found, ValueKind::F64);
+        assert_eq!(Table::new().values_as::<i64>(), Ok(vec![]));
+    }
+
+    #[test]
+    fn test_btree() {
+        let a = table!{
+            "name" => "Sven",
+            Value::Null => 1usize,
+            2.5 => table!{ "x" => 1usize },
+            Value::I64(-1) => vec![Value::Bool(true)],
+        };
+        let map = a.to_btree();
+        assert_eq!(map.len(), 4);
+        let keys: Vec<&Value> = map.keys().collect();
+        assert_eq!(keys, [&Value::Null, &Value::I64(-1), &Value::f64(2.5),
+            &Value::str("name")]);
+        let numbers: Vec<&Value> = map.range(Value::I64(0)..Value::str(""))
+            .map(|entry| entry.0)
+            .collect();
+        assert_eq!(numbers, [&Value::f64(2.5)]);
+        assert_eq!(Table::from_btree(map), a);
+        assert_eq!(Table::from_btree(BTreeMap::new()), Table::new());
+    }
+
+    #[test]
+    fn test_deep_clone() {
+        let a = table!{
+            "name" => "Sven Svensson Junior",
+            "tags" => vec![Value::str("a")],
+            "pos" => table!{ "x" => 1.0 },
+        };
+        let b = a.deep_clone();
+        assert_eq!(a, b);
+        match (&a["pos"], &b["pos"]) {
+            (&Value::Table(ref x), &Value::Table(ref y)) => assert!(!Arc::ptr_eq(x, y)),
+            _ => panic!("expected tables"),
+        }
+        match (&a["name"], &b["name"]) {
+            (&Value::String(ref x), &Value::String(ref y)) => assert!(!Str::ptr_eq(x, y)),
+            _ => panic!("expected strings"),
+        }
+        let c = Value::table(a.clone());
+        assert_eq!(c.deep_clone(), c);
+    }
+
+    #[test]
+    fn test_map_filter_retain() {
+        let a = table!{ "a" => 1, "b" => 2, "c" => 3 };
+        let doubled = a.map_values(|val| Value::I64(val.as_i64().unwrap() * 2));
+        assert_eq!(doubled, table!{ "a" => 2, "b" => 4, "c" => 6 });
+        assert_eq!(a.filter(|key, val| key != "a" && *val != 3i64), table!{ "b" => 2 });
+        assert_eq!(Table::new().map_values(|val| val.clone()), Table::new());
+
+        let mut b = a.clone();
+        b.retain(|_, val| {
+            *val = Value::I64(val.as_i64().unwrap() + 1);
+            *val != 2i64
+        });
+        assert_eq!(b, table!{ "b" => 3, "c" => 4 });
+    }
+
+    #[test]
+    fn test_partition() {
+        let a = table!{ "a" => 1, "b" => "x", "c" => 2.5, "d" => Value::Null };
+        let (numbers, rest) = a.clone().partition(|_, val| val.is_number());
+        assert_eq!(numbers, table!{ "a" => 1, "c" => 2.5 });
+        assert_eq!(rest, table!{ "b" => "x", "d" => Value::Null });
+        let mut merged = numbers;
+        merged.merge(rest);
+        assert_eq!(merged, a);
+
+        let (all, none) = a.clone().partition(|_, _| true);
+        assert_eq!((all, none), (a, Table::new()));
+    }
+
+    #[test]
+    fn test_kind_histogram() {
+        let a = table!{
+            "a" => 1usize,
+            "b" => 2usize,
+            "c" => "x",
+            "d" => table!{ "e" => 3usize, "f" => vec![Value::Null, Value::str("y")] },
+        };
+        let counts = a.kind_histogram();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&ValueKind::Usize], 2);
+        assert_eq!(counts[&ValueKind::String], 1);
+        assert_eq!(counts[&ValueKind::Table], 1);
+
+        let counts = a.deep_kind_histogram();
+        assert_eq!(counts.len(), 5);
+        assert_eq!(counts[&ValueKind::Usize], 3);
+        assert_eq!(counts[&ValueKind::String], 2);
+        assert_eq!(counts[&ValueKind::Table], 1);
+        assert_eq!(counts[&ValueKind::Array], 1);
+        assert_eq!(counts[&ValueKind::Null], 1);
+        assert!(Table::new().deep_kind_histogram().is_empty());
+    }
+
+    #[test]
+    fn test_rename_key_and_invert() {
+        let mut a = table!{ "a" => 1, "b" => 2 };
+        assert!(a.rename_key(&Value::str("a"), Value::str("c")));
+        assert!(!a.rename_key(&Value::str("a"), Value::str("d")));
+        assert!(a.rename_key(&Value::str("b"), Value::str("c")));
+        assert_eq!(a, table!{ "c" => 2 });
+
+        let b = table!{ "a" => "x", "b" => "y", "c" => "x" };
+        assert_eq!(b.invert(), table!{ "x" => "c", "y" => "b" });
+        assert_eq!(Table::new().invert(), Table::new());
+    }
+
+    #[test]
+    fn test_self_insert_is_acyclic() {
+        let mut a = Value::table(table!{ "x" => 1 });
+        let copy = a.clone();
+        a.as_table_mut().unwrap().insert(Value::str("self"), copy);
+        let inner = a.as_table_mut().unwrap()["self"].clone();
+        a.as_table_mut().unwrap()["self"].as_table_mut().unwrap()
+            .insert(Value::str("self"), inner);
+        assert_eq!(a.expect_table().get_path("self.self.x"), Some(&Value::I64(1)));
+        assert_eq!(a.expect_table().get_path("self.self.self"), None);
+        assert_eq!(a, a.clone());
+        assert!(format!("{:?}", a).contains("\"x\""));
+    }
+
+    #[test]
+    fn test_group_by() {
+        let a = table!{
+            0 => table!{ "name" => "Sven", "team" => "red" },
+            1 => table!{ "name" => "Ida", "team" => "blue" },
+            2 => table!{ "name" => "Bo", "team" => "red" },
+            3 => table!{ "name" => "Eli" },
+        };
+        let groups = a.group_by(|_, row| row.get("team").cloned().unwrap_or(Value::Null));
+        assert_eq!(groups, table!{
+            "red" => table!{ 0 => a[0].clone(), 2 => a[2].clone() },
+            "blue" => table!{ 1 => a[1].clone() },
+            Value::Null => table!{ 3 => a[3].clone() },
+        });
+        assert_eq!(Table::new().group_by(|key, _| key.clone()), Table::new());
+
+        let numbers = table!{ "a" => 1usize, "b" => 1.0, "c" => 2usize };
+        let groups = numbers.group_by(|_, val| val.clone());
+        assert_eq!(groups.len(), 2);
+        let one = groups.keys().find(|key| **key == 1usize).unwrap();
+        assert_eq!(one.kind(), ValueKind::Usize);
+    }
+
+    #[test]
+    fn test_char() {
+        let a = Value::from('x');
+        assert_eq!(a.as_char(), Some('x'));
+        assert_eq!(a.kind(), ValueKind::Char);
+        assert_eq!(a, 'x');
+        assert_ne!(a, Value::str("x"));
+        assert_eq!(Value::str("x").as_char(), None);
+        assert!(Value::Char('a') < Value::Char('b'));
+        assert!(Value::f64(1e300) < Value::Char('\0'));
+        assert!(Value::Char('z') < Value::str(""));
+        assert_eq!(a.to_string(), "x");
+        assert_eq!(table!{ "c" => 'x' }.to_string(), "{ \"c\": 'x' }");
+        assert_eq!(char::try_from(&a), Ok('x'));
+        assert_eq!(Value::Char('"').to_json_string(), "\"\\\"\"");
+
+        let mut b = table!{ 'a' => 1, "a" => 2 };
+        assert_eq!(b.len(), 2);
+        b.insert(Value::Char('a'), Value::Usize(3));
+        assert_eq!(b[Value::Char('a')], Value::Usize(3));
+        assert_eq!(b["a"], Value::I64(2));
+    }
+
+    #[test]
+    fn test_debug_sorted() {
+        let keys = ["d", "a", "c", "b", "e", "f", "g", "h"];
+        let forward: Table = keys.iter().map(|&key| (key, 1usize)).collect();
+        let backward: Table = keys.iter().rev().map(|&key| (key, 1usize)).collect();
+        assert_eq!(format!("{:?}", forward), format!("{:?}", backward));
+
+        let a = table!{ "b" => table!{ "x" => 1usize }, "a" => Value::Null };
+        assert_eq!(format!("{:?}", a), "Table({String(\"a\"): Null, \
+            String(\"b\"): Table(Table({String(\"x\"): Usize(1)}))})");
+        assert_eq!(format!("{:#?}", a), r#"Table(
+    {
+        String(
+            "a",
+        ): Null,
+        String(
+            "b",
+        ): Table(
+            Table(
+                {
+                    String(
+                        "x",
+                    ): Usize(
+                        1,
+                    ),
+                },
+            ),
+        ),
+    },
+)"#);
+        assert_eq!(format!("{:?}", Table::new()), "Table({})");
+    }
+
+    #[test]
+    fn test_narrowing_conversions() {
+        assert_eq!(Value::Usize(200).to_u8_checked(), Some(200));
+        assert_eq!(Value::I64(256).to_u8_checked(), None);
+        assert_eq!(Value::I64(-1).to_u8_checked(), None);
+        assert_eq!(Value::I64(-1).to_u8_saturating(), Some(0));
+        assert_eq!(Value::U64(u64::MAX).to_i32_checked(), None);
+        assert_eq!(Value::U64(u64::MAX).to_i32_saturating(), Some(i32::MAX));
+        assert_eq!(Value::I64(i64::MIN).to_i32_checked(), None);
+        assert_eq!(Value::I64(i64::MIN).to_i32_saturating(), Some(i32::MIN));
+        assert_eq!(Value::I64(-5).to_i8_checked(), Some(-5));
+        assert_eq!(Value::U64(u64::MAX).to_i64_saturating(), Some(i64::MAX));
+        assert_eq!(Value::U64(u64::MAX).to_u64_checked(), Some(u64::MAX));
+        assert_eq!(Value::I64(70_000).to_u16_saturating(), Some(u16::MAX));
+        assert_eq!(Value::f64(1.0).to_u8_checked(), None);
+        assert_eq!(Value::str("1").to_i32_saturating(), None);
+    }
+
+    #[test]
+    fn test_as_number() {
+        let column = [Value::Usize(1), Value::U64(2), Value::I64(-3), Value::f64(0.5)];
+        let sum: f64 = column.iter().filter_map(Value::as_number).sum();
+        assert_eq!(sum, 0.5);
+        assert_eq!(Value::str("1").as_number(), None);
+
+        assert_eq!(Value::U64(u64::MAX).as_integer(), Some(u64::MAX as i128));
+        assert_eq!(Value::I64(i64::MIN).as_integer(), Some(i64::MIN as i128));
+        assert_eq!(Value::f64(1.0).as_integer(), None);
+        assert_eq!(Value::Null.as_integer(), None);
+    }
+
+    #[test]
+    fn test_is_truthy() {
+        for val in &[
+            Value::Null, Value::Bool(false), Value::Usize(0), Value::U64(0),
+            Value::I64(0), Value::f64(-0.0), Value::str(""), Value::array(vec![]),
+            Value::table(Table::new()),
+        ] {
+            assert!(!val.is_truthy(), "{:?}", val);
+        }
+        for val in &[
+            Value::Bool(true), Value::I64(-1), Value::f64(f64::NAN), Value::str("0"),
+            Value::array(vec![Value::Null]), Value::table(table!{ "a" => Value::Null }),
+        ] {
+            assert!(val.is_truthy(), "{:?}", val);
+        }
+    }
+
+    #[test]
+    fn test_bytes() {
+        let a = Value::from(vec![0xffu8, 0xfe]);
+        let b = Value::from(&b"\xff\xfe"[..]);
+        assert_eq!(a, b);
+        assert!(a.is_bytes());
+        assert_eq!(a.as_bytes(), Some(&[0xff, 0xfe][..]));
+        assert_eq!(a.kind(), ValueKind::Bytes);
+        assert_eq!(a.len(), Some(2));
+        assert!(a != Value::str("\u{ff}\u{fe}"));
+        assert!(Value::str("z") < a && a < Value::array(vec![]));
+        assert_eq!(a.to_string(), "//4=");
+        assert_eq!(table!{ "a" => a.clone() }.to_string(), "{ \"a\": \"//4=\" }");
+        assert_eq!(a.to_json_string(), "\"//4=\"");
+
+        let mut t = Table::new();
+        t.insert(a.clone(), Value::Bool(true));
+        assert_eq!(t.get(&b), Some(&Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_parse_typed() {
+        assert!(Value::parse_typed("null").is_null());
+        assert_eq!(Value::parse_typed("true"), Value::Bool(true));
+        assert!(matches!(Value::parse_typed("42"), Value::Usize(42)));
+        assert!(matches!(Value::parse_typed("-42"), Value::I64(-42)));
+        assert!(matches!(Value::parse_typed("1.5"), Value::F64(_)));
+        assert_eq!(Value::parse_typed("1e3"), Value::f64(1000.0));
+        for text in &["", "inf", "NaN", " 1", "True", "nulls", "1.2.3"] {
+            assert_eq!(Value::parse_typed(text), Value::str(text));
+        }
+        assert_eq!("false".parse::<Value>(), Ok(Value::Bool(false)));
+    }
+
+    #[test]
+    fn test_get_or() {
+        let a = table!{
+            "name" => "Sven",
+            "alive" => true,
+            "level" => 3usize,
+            "depth" => -2,
+            "speed" => 1.5,
+        };
+        let fallback = Value::Null;
+        assert_eq!(a.get_or(&Value::str("name"), &fallback), &Value::str("Sven"));
+        assert_eq!(a.get_or(&Value::str("nick"), &fallback), &Value::Null);
+        assert_eq!(a.get_str_or("name", "?"), "Sven");
+        assert_eq!(a.get_str_or("level", "?"), "?");
+        assert!(a.get_bool_or("alive", false));
+        assert!(a.get_bool_or("dead", true));
+        assert_eq!(a.get_f64_or("speed", 0.0), 1.5);
+        assert_eq!(a.get_f64_or("level", 0.0), 3.0);
+        assert_eq!(a.get_f64_or("name", 0.0), 0.0);
+        assert_eq!(a.get_usize_or("level", 0), 3);
+        assert_eq!(a.get_u64_or("level", 0), 3);
+        assert_eq!(a.get_i64_or("level", 0), 3);
+        assert_eq!(a.get_i64_or("depth", 0), -2);
+        assert_eq!(a.get_usize_or("depth", 9), 9);
+        assert_eq!(a.get_usize_or("speed", 9), 9);
+    }
+
+    #[test]
+    fn test_eq_ignoring_nulls() {
+        let a = table!{
+            "name" => "Sven",
+            "pos" => table!{ "x" => 1.0 },
+            "list" => vec![Value::table(table!{ "a" => 1usize })],
+        };
+        let b = table!{
+            "name" => "Sven",
+            "pos" => table!{ "x" => 1.0, "y" => Value::Null },
+            "list" => vec![Value::table(table!{ "a" => 1usize, "b" => Value::Null })],
+            "level" => Value::Null,
+        };
+        assert!(a != b);
+        assert!(a.eq_ignoring_nulls(&b));
+        assert!(b.eq_ignoring_nulls(&a));
+        assert!(Table::new().eq_ignoring_nulls(&table!{ "x" => Value::Null }));
+        assert!(table!{ "x" => Value::Null }.eq_ignoring_nulls(&Table::new()));
+
+        let c = table!{ "name" => "Sven", "pos" => table!{ "x" => 2.0 } };
+        assert!(!a.eq_ignoring_nulls(&c));
+        assert!(!c.eq_ignoring_nulls(&b));
+        let d = table!{ "x" => 1usize };
+        assert!(!d.eq_ignoring_nulls(&table!{ "x" => Value::Null }));
+        assert!(!table!{ "x" => Value::Null }.eq_ignoring_nulls(&d));
+        let e = table!{ "v" => vec![Value::Null] };
+        assert!(!e.eq_ignoring_nulls(&table!{ "v" => Vec::<Value>::new() }));
+        assert!(table!{ "x" => 1.0 }.eq_ignoring_nulls(&table!{ "x" => 1usize }));
+    }
+
+    #[test]
+    fn test_canonicalize() {
+        assert!(matches!(Value::U64(3).canonicalize(), Value::Usize(3)));
+        assert!(matches!(Value::I64(3).canonicalize(), Value::Usize(3)));
+        assert!(matches!(Value::f64(3.0).canonicalize(), Value::Usize(3)));
+        assert!(matches!(Value::f64(-0.0).canonicalize(), Value::Usize(0)));
+        assert!(matches!(Value::f64(-3.0).canonicalize(), Value::I64(-3)));
+        assert!(matches!(Value::f64(3.5).canonicalize(), Value::F64(_)));
+        assert!(matches!(Value::f64(1e30).canonicalize(), Value::F64(_)));
+        assert!(matches!(Value::f64(f64::NAN).canonicalize(), Value::F64(_)));
+
+        let a = table!{ 1u64 => table!{ "x" => 2.0 }, "v" => vec![Value::I64(4)] };
+        let b = table!{ 1.0 => table!{ "x" => 2i64 }, "v" => vec![Value::f64(4.0)] };
+        assert_eq!(format!("{:?}", a.canonicalize().sorted_entries()),
+            format!("{:?}", b.canonicalize().sorted_entries()));
+    }
+
+    #[test]
+    fn test_simplify() {
+        let a = Value::table(table!{
+            "u" => Value::U64(7),
+            "i" => Value::I64(-7),
+            "f" => 2.0,
+            "g" => 2.5,
+            "n" => Value::Null,
+            "t" => table!{ "n" => Value::Null, "big" => Value::f64(-1e10) },
+            "v" => vec![Value::Null, Value::U64(1)],
+        });
+        let kinds = |val: &Value| -> Vec<ValueKind> {
+            val.as_table().unwrap().sorted_entries().into_iter()
+                .map(|(_, val)| val.kind()).collect()
+        };
+        let simple = a.simplify(true);
+        assert_eq!(kinds(&simple), [ValueKind::Usize, ValueKind::F64, ValueKind::I64,
+            ValueKind::Table, ValueKind::Usize, ValueKind::Array]);
+        let t = simple.get("t").unwrap();
+        assert_eq!(t.len(), Some(1));
+        assert!(matches!(t.get("big"), Some(&Value::I64(-10000000000))));
+        let v = Value::array(vec![Value::Null, Value::Usize(1)]);
+        assert_eq!(simple.get("v"), Some(&v));
+        assert_eq!(a.simplify(false), a.canonicalize());
+        assert_eq!(kinds(&a.simplify(false)).len(), 7);
+    }
+
+    #[test]
+    fn test_memory_usage() {
+        assert_eq!(Value::Usize(1).memory_usage(), 0);
+        assert_eq!(Value::str("short").memory_usage(), 0);
+        let long = "a string too long to be inline";
+        assert!(Value::str(long).memory_usage() >= long.len());
+        assert!(Value::from(vec![0u8; 100]).memory_usage() >= 100);
+        assert!(Value::array(vec![Value::Null; 4]).memory_usage() >= 4 * 16);
+
+        let mut a = Table::new();
+        let mut last = a.memory_usage();
+        for i in 0..100 {
+            a.insert(Value::Usize(i), Value::str(long));
+            let usage = a.memory_usage();
+            assert!(usage > last);
+            last = usage;
+        }
+        let nested = Value::table(a.clone());
+        let twice = table!{ "a" => nested.clone(), "b" => nested };
+        assert!(twice.memory_usage() > 2 * a.memory_usage());
+    }
+
+    #[test]
+    fn test_expect() {
+        let a = table!{ "name" => "Sven", "level" => 3usize };
+        let val = Value::table(a.clone());
+        assert_eq!(val.expect_table(), &a);
+        assert_eq!(a["name"].expect_str(), "Sven");
+        assert_eq!(a["level"].expect_usize(), 3);
+        assert!(Value::Bool(true).expect_bool());
+        assert!(Value::array(vec![]).expect_array().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "expected string, found table")]
+    fn test_expect_mismatch() {
+        Value::table(Table::new()).expect_str();
+    }
+
+    #[test]
+    fn test_as_map() {
+        let mut map = HashMap::new();
+        map.insert(Value::str("x"), Value::Usize(1));
+        let mut a = Table::from(map.clone());
+        assert_eq!(a.as_map(), &map);
+        assert_eq!(a.as_ref(), &map);
+        a.as_map_mut().insert(Value::str("y"), Value::Usize(2));
+        a.as_mut().remove(&Value::str("x"));
+        let map = a.into_inner();
+        assert_eq!(map.len(), 1);
+        assert_eq!(map[&Value::str("y")], Value::Usize(2));
+    }
+
+    #[test]
+    fn test_from_map() {
+        let mut scores: HashMap<String, f64> = HashMap::new();
+        scores.insert("a".into(), 1.5);
+        scores.insert("b".into(), 2.0);
+        let a: Table = scores.into();
+        assert_eq!(a, table!{ "a" => 1.5, "b" => 2.0 });
+
+        let mut names: BTreeMap<usize, &str> = BTreeMap::new();
+        names.insert(0, "x");
+        names.insert(1, "y");
+        let b = Table::from(names);
+        assert!(b.is_array());
+        assert_eq!(b[1], Value::str("y"));
+        assert_eq!(Table::from(HashMap::<Value, Value>::new()), Table::new());
+    }
+
+    #[test]
+    fn test_shrink_to_fit() {
+        let mut a: Table = (0..1000).map(|i| (i, i)).collect();
+        let full = a.capacity();
+        assert!(full >= 1000);
+        a.retain(|key, _| key.as_i64().unwrap() < 10);
+        assert!(a.capacity() >= 1000);
+        a.shrink_to_fit();
+        assert!(a.capacity() >= 10 && a.capacity() < 100);
+        assert_eq!(a.len(), 10);
+    }
+
+    #[test]
+    fn test_reserve_and_clear() {
+        let mut a = Table::new();
+        a.reserve(1000);
+        assert!(a.capacity() >= 1000);
+        for i in 0..1000 {
+            a.push(Value::Usize(i));
+        }
+        a.clear();
+        assert!(a.is_empty());
+        assert!(a.capacity() >= 1000);
+
+        a.extend((0..10).map(|i| (i, i)));
+        a.clear_and_shrink();
+        assert!(a.is_empty());
+        assert_eq!(a.capacity(), 0);
+        a.push(Value::Null);
+        assert_eq!(a.len(), 1);
     }
 
     #[bench]
@@ -356,5 +3324,56 @@ mod tests {
             Arc::make_mut(&mut a)[0] = Value::f64(1.0);
         });
     }
-}
 
+    #[bench]
+    fn bench_insert_short_keys(bencher: &mut Bencher) {
+        let keys: Vec<String> = (0..1000).map(|i| format!("k{}", i)).collect();
+        bencher.iter(|| {
+            let mut a = Table::with_capacity(keys.len());
+            for key in &keys {
+                a.insert(Value::str(key), Value::Bool(true));
+            }
+            a
+        });
+    }
+
+    fn bench_clone(bencher: &mut Bencher, val: Value) {
+        use tests::test::black_box;
+
+        bencher.iter(|| {
+            for _ in 0..100 {
+                black_box(black_box(&val).clone());
+            }
+        });
+    }
+
+    #[bench]
+    fn bench_clone_usize(bencher: &mut Bencher) {
+        bench_clone(bencher, Value::Usize(1));
+    }
+
+    #[bench]
+    fn bench_clone_short_string(bencher: &mut Bencher) {
+        bench_clone(bencher, Value::str("short"));
+    }
+
+    #[bench]
+    fn bench_clone_long_string(bencher: &mut Bencher) {
+        bench_clone(bencher, Value::str("a string too long to be inline"));
+    }
+
+    #[bench]
+    fn bench_clone_bytes(bencher: &mut Bencher) {
+        bench_clone(bencher, Value::from(vec![0u8; 16]));
+    }
+
+    #[bench]
+    fn bench_clone_array(bencher: &mut Bencher) {
+        bench_clone(bencher, Value::array(vec![Value::Null; 16]));
+    }
+
+    #[bench]
+    fn bench_clone_table(bencher: &mut Bencher) {
+        bench_clone(bencher, Value::table(table!{ "x" => 1.0, "y" => 2.0 }));
+    }
+}
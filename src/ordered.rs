@@ -0,0 +1,370 @@
+//! Tables that remember insertion order.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{ Hash, Hasher };
+use std::iter::FromIterator;
+use std::ops::{ Index, IndexMut };
+use std::{ slice, vec };
+
+use key::Key;
+use path::{ lookup, parse_index };
+use { fmt_entries, hash_entries, Table, Value };
+
+/// A table that iterates in insertion order.
+///
+/// `Table` is backed by a `HashMap`, so its iteration order is arbitrary.
+/// `OrderedTable` keeps its entries in a `Vec` with a hash index on the
+/// side, so iteration, and anything built on it, is reproducible. Lookups
+/// are about as fast as in `Table`, but `remove` takes linear time since it
+/// keeps the remaining entries in order.
+///
+/// Equality and hashing ignore order, like for `Table`.
+///
+/// The type covers what order matters for: building a table, iterating
+/// over it, `get_path`, `merge`, JSON output and `Display`. It is not a
+/// drop-in replacement for `Table`. Nested tables are ordinary `Table`s,
+/// since `Value` has no ordered variant and adding one would grow every
+/// exhaustive match in the crate, so the order only holds at the top
+/// level. The remaining methods of `Table`, like `set_path`, `pointer` and
+/// `entry`, are left out; convert with `Table::from` to use them.
+#[derive(Clone, Debug, Default)]
+pub struct OrderedTable {
+    entries: Vec<(Value, Value)>,
+    indices: HashMap<Value, usize>,
+}
+
+impl OrderedTable {
+    /// Creates new ordered table.
+    pub fn new() -> OrderedTable {
+        OrderedTable { entries: vec![], indices: HashMap::new() }
+    }
+
+    /// Creates new ordered table with capacity.
+    pub fn with_capacity(capacity: usize) -> OrderedTable {
+        OrderedTable {
+            entries: Vec::with_capacity(capacity),
+            indices: HashMap::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if the table has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Inserts an entry, returning the previous value under the key.
+    ///
+    /// A new key is appended at the end. Overwriting an existing key keeps
+    /// its position.
+    pub fn insert(&mut self, key: Value, val: Value) -> Option<Value> {
+        if let Some(&i) = self.indices.get(&key) {
+            return Some(::std::mem::replace(&mut self.entries[i].1, val));
+        }
+        self.indices.insert(key.clone(), self.entries.len());
+        self.entries.push((key, val));
+        None
+    }
+
+    /// Removes an entry, keeping the order of the remaining entries.
+    pub fn remove(&mut self, key: &Value) -> Option<Value> {
+        let i = self.indices.remove(key)?;
+        let (_, val) = self.entries.remove(i);
+        for &(ref key, _) in &self.entries[i..] {
+            *self.indices.get_mut(key).unwrap() -= 1;
+        }
+        Some(val)
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.indices.clear();
+    }
+
+    /// Returns `true` if the table has an entry under the key.
+    pub fn contains_key(&self, key: &Value) -> bool {
+        self.indices.contains_key(key)
+    }
+
+    /// Returns the value stored under a key, or `None` if missing.
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.indices.get(key).map(|&i| &self.entries[i].1)
+    }
+
+    /// Mutably borrows the value stored under a key.
+    pub fn get_mut(&mut self, key: &Value) -> Option<&mut Value> {
+        let i = *self.indices.get(key)?;
+        Some(&mut self.entries[i].1)
+    }
+
+    /// Returns the value stored under an integer key, or `None` if missing.
+    pub fn get_usize(&self, key: usize) -> Option<&Value> {
+        self.get(&Value::Usize(key))
+    }
+
+    /// Returns the value stored under a string key, or `None` if missing.
+    pub fn get_str(&self, key: &str) -> Option<&Value> {
+        self.indices.get(&key as &dyn Key).map(|&i| &self.entries[i].1)
+    }
+
+    /// Iterates over the entries in insertion order.
+    pub fn iter<'a>(&'a self) -> Iter<'a> {
+        self.entries.iter().map(|&(ref key, ref val)| (key, val))
+    }
+
+    /// Iterates over the keys in insertion order.
+    pub fn keys(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|entry| &entry.0)
+    }
+
+    /// Iterates over the values in insertion order.
+    pub fn values(&self) -> impl Iterator<Item = &Value> {
+        self.entries.iter().map(|entry| &entry.1)
+    }
+
+    /// Mutably iterates over the values in insertion order.
+    pub fn values_mut(&mut self) -> impl Iterator<Item = &mut Value> {
+        self.entries.iter_mut().map(|entry| &mut entry.1)
+    }
+
+    /// Returns the value at a dotted path, see `Table::get_path`.
+    ///
+    /// The first segment looks up an entry of this table, and the others
+    /// look into nested tables and arrays.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        let mut segments = path.split('.');
+        let first = segments.next()?;
+        let mut val = self.get_str(first).or_else(|| {
+            parse_index(first).and_then(|index| self.get_usize(index))
+        })?;
+        for segment in segments {
+            val = lookup(val, segment)?;
+        }
+        Some(val)
+    }
+
+    /// Inserts all entries of another table in its order, overwriting on
+    /// collision.
+    ///
+    /// Overwritten keys keep their position, and new keys are appended.
+    pub fn merge(&mut self, other: OrderedTable) {
+        self.extend(other);
+    }
+
+    fn entry_or_null(&mut self, key: Value) -> &mut Value {
+        let i = match self.indices.get(&key) {
+            Some(&i) => i,
+            None => {
+                self.insert(key, Value::Null);
+                self.entries.len() - 1
+            }
+        };
+        &mut self.entries[i].1
+    }
+}
+
+/// Iterator over the entries of an `OrderedTable`.
+pub type Iter<'a> = ::std::iter::Map<
+    slice::Iter<'a, (Value, Value)>,
+    fn(&'a (Value, Value)) -> (&'a Value, &'a Value)
+>;
+
+impl PartialEq for OrderedTable {
+    fn eq(&self, other: &OrderedTable) -> bool {
+        self.len() == other.len() &&
+        self.iter().all(|(key, val)| other.get(key) == Some(val))
+    }
+}
+
+impl Eq for OrderedTable {}
+
+impl Hash for OrderedTable {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        hash_entries(self.iter(), state);
+    }
+}
+
+/// Writes the table like `Display for Table`, but in insertion order.
+impl fmt::Display for OrderedTable {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt_entries(f, self.iter().collect(), 0)
+    }
+}
+
+impl Index<Value> for OrderedTable {
+    type Output = Value;
+
+    fn index(&self, index: Value) -> &Value {
+        self.get(&index).unwrap()
+    }
+}
+
+impl Index<usize> for OrderedTable {
+    type Output = Value;
+
+    fn index(&self, index: usize) -> &Value {
+        self.get_usize(index).unwrap()
+    }
+}
+
+impl<'b> Index<&'b str> for OrderedTable {
+    type Output = Value;
+
+    fn index(&self, index: &'b str) -> &Value {
+        self.get_str(index).unwrap()
+    }
+}
+
+impl IndexMut<Value> for OrderedTable {
+    fn index_mut(&mut self, index: Value) -> &mut Value {
+        self.entry_or_null(index)
+    }
+}
+
+impl IndexMut<usize> for OrderedTable {
+    fn index_mut(&mut self, index: usize) -> &mut Value {
+        self.entry_or_null(Value::Usize(index))
+    }
+}
+
+impl<'b> IndexMut<&'b str> for OrderedTable {
+    fn index_mut(&mut self, index: &'b str) -> &mut Value {
+        self.entry_or_null(Value::str(index))
+    }
+}
+
+impl<K, V> FromIterator<(K, V)> for OrderedTable
+    where K: Into<Value>, V: Into<Value>
+{
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> OrderedTable {
+        let mut table = OrderedTable::new();
+        table.extend(iter);
+        table
+    }
+}
+
+impl<K, V> Extend<(K, V)> for OrderedTable
+    where K: Into<Value>, V: Into<Value>
+{
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        for (key, val) in iter {
+            self.insert(key.into(), val.into());
+        }
+    }
+}
+
+impl IntoIterator for OrderedTable {
+    type Item = (Value, Value);
+    type IntoIter = vec::IntoIter<(Value, Value)>;
+
+    fn into_iter(self) -> vec::IntoIter<(Value, Value)> {
+        self.entries.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a OrderedTable {
+    type Item = (&'a Value, &'a Value);
+    type IntoIter = Iter<'a>;
+
+    fn into_iter(self) -> Iter<'a> {
+        self.iter()
+    }
+}
+
+/// Orders the entries by key, since a `Table` has no order to keep.
+impl From<Table> for OrderedTable {
+    fn from(table: Table) -> OrderedTable {
+        let mut entries: Vec<_> = table.0.into_iter().collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        entries.into_iter().collect()
+    }
+}
+
+impl From<OrderedTable> for Table {
+    fn from(table: OrderedTable) -> Table {
+        table.entries.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn test_ordered_table() {
+        let mut a = OrderedTable::new();
+        a["z"] = Value::Usize(1);
+        a["a"] = Value::Usize(2);
+        a[5] = Value::Usize(3);
+        a["z"] = Value::Usize(4);
+        assert_eq!(a.len(), 3);
+        assert_eq!(a["z"], Value::Usize(4));
+        let keys: Vec<_> = a.keys().cloned().collect();
+        assert_eq!(keys, vec![Value::str("z"), Value::str("a"), Value::Usize(5)]);
+
+        assert_eq!(a.remove(&Value::str("z")), Some(Value::Usize(4)));
+        assert_eq!(a.remove(&Value::str("z")), None);
+        a.insert(Value::str("b"), Value::Null);
+        let entries: Vec<_> = a.iter().map(|(key, _)| key.to_string()).collect();
+        assert_eq!(entries, vec!["a", "5", "b"]);
+        assert_eq!(a.get_usize(5), Some(&Value::Usize(3)));
+        assert_eq!(a.get_str("b"), Some(&Value::Null));
+    }
+
+    #[test]
+    fn test_ordered_table_conversions() {
+        let table = table!{ "b" => 1, "a" => 2, 0 => 3 };
+        let ordered = OrderedTable::from(table.clone());
+        let keys: Vec<_> = ordered.keys().cloned().collect();
+        assert_eq!(keys, vec![Value::Usize(0), Value::str("a"), Value::str("b")]);
+        assert_eq!(Table::from(ordered.clone()), table);
+
+        let reversed: OrderedTable = ordered.clone().into_iter().rev().collect();
+        assert_eq!(reversed, ordered);
+        assert!(reversed.iter().ne(ordered.iter()));
+    }
+
+    #[test]
+    fn test_ordered_table_output() {
+        let mut a = OrderedTable::new();
+        a["z"] = Value::Usize(1);
+        a["pos"] = Value::table(table!{ "y" => 2usize, "x" => 1usize });
+        a[0] = Value::array(vec![Value::str("v")]);
+        assert_eq!(a.get_path("pos.x"), Some(&Value::Usize(1)));
+        assert_eq!(a.get_path("0.0"), Some(&Value::str("v")));
+        assert_eq!(a.get_path("pos.z"), None);
+
+        assert_eq!(a.to_json_string(), r#"{"z":1,"pos":{"x":1,"y":2},"0":["v"]}"#);
+        assert_eq!(a.to_string(), r#"{ "z": 1, "pos": { "x": 1, "y": 2 }, 0: ["v"] }"#);
+        assert_eq!(a.to_json_pretty(), r#"{
+  "z": 1,
+  "pos": {
+    "x": 1,
+    "y": 2
+  },
+  "0": [
+    "v"
+  ]
+}"#);
+
+        let b: OrderedTable = vec![("pos", 3usize), ("new", 4usize)]
+            .into_iter().collect();
+        a.merge(b.clone());
+        let keys: Vec<_> = a.keys().cloned().collect();
+        assert_eq!(keys, vec![Value::str("z"), Value::str("pos"), Value::Usize(0),
+            Value::str("new")]);
+        assert_eq!(a["pos"], Value::Usize(3));
+
+        let reversed: OrderedTable = b.clone().into_iter().rev().collect();
+        let set: HashSet<OrderedTable> = vec![b, reversed].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
+}
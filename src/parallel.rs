@@ -0,0 +1,99 @@
+//! Parallel operations on tables, enabled by the `parallel` feature.
+
+use std::thread;
+
+use { Table, Value };
+
+impl Table {
+    /// Works like `map_values`, but calls the closure from several threads.
+    ///
+    /// The entries are split into one chunk per available CPU, each mapped
+    /// on its own scoped thread. This only pays off when the closure is
+    /// expensive compared to starting threads.
+    pub fn par_map_values<F>(&self, f: F) -> Table
+        where F: Fn(&Value) -> Value + Sync
+    {
+        let chunks = self.par_fold(Vec::new, |mut out, key, val| {
+            out.push((key.clone(), f(val)));
+            out
+        }, |mut a, b| {
+            a.extend(b);
+            a
+        });
+        let mut out = Table::with_capacity(self.len());
+        out.0.extend(chunks);
+        out
+    }
+
+    /// Folds the entries on several threads and combines the results.
+    ///
+    /// Each thread starts from `identity()` and folds its share of the
+    /// entries with `fold`. The per-thread results are then combined with
+    /// `reduce`, which should be associative. Entries are visited in no
+    /// particular order.
+    pub fn par_fold<T, I, F, R>(&self, identity: I, fold: F, reduce: R) -> T
+        where T: Send,
+              I: Fn() -> T + Sync,
+              F: Fn(T, &Value, &Value) -> T + Sync,
+              R: Fn(T, T) -> T
+    {
+        let entries: Vec<(&Value, &Value)> = self.0.iter().collect();
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+        let chunk_len = entries.len().div_ceil(threads).max(1);
+        let (identity, fold) = (&identity, &fold);
+        let results: Vec<T> = thread::scope(|scope| {
+            let handles: Vec<_> = entries.chunks(chunk_len).map(|chunk| {
+                scope.spawn(move || {
+                    chunk.iter().fold(identity(), |acc, &(key, val)| fold(acc, key, val))
+                })
+            }).collect();
+            handles.into_iter().map(|handle| handle.join().unwrap()).collect()
+        });
+        results.into_iter().fold(identity(), reduce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use self::test::Bencher;
+
+    fn expensive(val: &Value) -> Value {
+        let x = val.as_usize().unwrap();
+        Value::Usize((0..1000).fold(x, |acc, i| acc.wrapping_mul(31).wrapping_add(i)))
+    }
+
+    fn large() -> Table {
+        (0..10_000usize).map(|i| (i, i)).collect()
+    }
+
+    #[test]
+    fn test_par_map_values() {
+        let a = large();
+        assert_eq!(a.par_map_values(expensive), a.map_values(expensive));
+        assert_eq!(Table::new().par_map_values(expensive), Table::new());
+    }
+
+    #[test]
+    fn test_par_fold() {
+        let a = large();
+        let sum = a.par_fold(|| 0, |acc, _, val| acc + val.as_usize().unwrap(),
+            |a, b| a + b);
+        assert_eq!(sum, (0..10_000).sum::<usize>());
+        assert_eq!(Table::new().par_fold(|| 1, |acc, _, _| acc, |a, b| a * b), 1);
+    }
+
+    #[bench]
+    fn bench_map_values(bencher: &mut Bencher) {
+        let a = large();
+        bencher.iter(|| a.map_values(expensive));
+    }
+
+    #[bench]
+    fn bench_par_map_values(bencher: &mut Bencher) {
+        let a = large();
+        bencher.iter(|| a.par_map_values(expensive));
+    }
+}
@@ -0,0 +1,602 @@
+//! Access to nested values by path.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use walk::PathSegment;
+use { Table, Value, ValueKind };
+
+/// An error returned when a path runs into a value that is not a table.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct PathError {
+    /// The index of the path segment holding the value.
+    pub segment: usize,
+    /// The kind of value found there.
+    pub found: ValueKind,
+}
+
+impl fmt::Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "expected table at path segment {}, found {}",
+            self.segment, self.found)
+    }
+}
+
+impl Error for PathError {}
+
+/// Selects an entry of a table or an element of an array.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum Key<'a> {
+    /// A string key of a table.
+    Str(&'a str),
+    /// An integer key of a table or an index into an array.
+    Usize(usize),
+    /// Any key of a table, or a whole number indexing an array.
+    Value(&'a Value),
+}
+
+impl<'a> From<&'a str> for Key<'a> {
+    fn from(key: &'a str) -> Key<'a> {
+        Key::Str(key)
+    }
+}
+
+impl<'a> From<usize> for Key<'a> {
+    fn from(key: usize) -> Key<'a> {
+        Key::Usize(key)
+    }
+}
+
+impl<'a> From<&'a Value> for Key<'a> {
+    fn from(key: &'a Value) -> Key<'a> {
+        Key::Value(key)
+    }
+}
+
+impl Value {
+    /// Returns an entry of a table or an element of an array.
+    ///
+    /// Returns `None` if the entry is missing or the value is neither a
+    /// table nor an array. This allows chaining lookups into nested data:
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::Value;
+    ///
+    /// # fn main() {
+    /// let val = Value::table(table!{ "a" => vec![Value::from(1), Value::from(2)] });
+    /// assert_eq!(val.get("a").and_then(|a| a.get(1)), Some(&2.into()));
+    /// # }
+    /// ```
+    pub fn get<'a, K: Into<Key<'a>>>(&self, key: K) -> Option<&Value> {
+        match (self, key.into()) {
+            (&Value::Table(ref table), Key::Str(key)) => table.get_str(key),
+            (&Value::Table(ref table), Key::Usize(key)) => table.get_usize(key),
+            (&Value::Table(ref table), Key::Value(key)) => table.get_value(key),
+            (&Value::Array(ref items), Key::Usize(index)) => items.get(index),
+            (&Value::Array(ref items), Key::Value(key)) => {
+                key.to_index().and_then(|index| items.get(index))
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the value at a path of keys and indices.
+    ///
+    /// Each segment is looked up with `get`, so `Key` selects a table
+    /// entry by any key, including strings that contain `.`, and `Index`
+    /// selects an array element or an integer key of a table. An empty
+    /// path selects this value. Paths reported by `walk` lead back to
+    /// their values this way.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::{ PathSegment, Value };
+    ///
+    /// # fn main() {
+    /// let val = Value::table(table!{ "a.b" => vec![Value::from("x")] });
+    /// let path = [PathSegment::Key("a.b".into()), PathSegment::Index(0)];
+    /// assert_eq!(val.get_in(&path), Some(&"x".into()));
+    /// # }
+    /// ```
+    pub fn get_in(&self, path: &[PathSegment]) -> Option<&Value> {
+        path.iter().try_fold(self, |val, segment| match *segment {
+            PathSegment::Key(ref key) => val.get(key),
+            PathSegment::Index(index) => val.get(index),
+        })
+    }
+
+    /// Mutably borrows the value at a path of keys and indices, see
+    /// `get_in`.
+    ///
+    /// Shared tables and arrays along the path are cloned first, but only
+    /// if the whole path exists.
+    pub fn get_in_mut(&mut self, path: &[PathSegment]) -> Option<&mut Value> {
+        // Check first, so that shared tables are not cloned for nothing.
+        self.get_in(path)?;
+        path.iter().try_fold(self, |val, segment| match (val, segment) {
+            (&mut Value::Table(ref mut table), &PathSegment::Key(ref key)) => {
+                Arc::make_mut(table).get_mut(key)
+            }
+            (&mut Value::Table(ref mut table), &PathSegment::Index(index)) => {
+                Arc::make_mut(table).get_mut(&Value::Usize(index))
+            }
+            (&mut Value::Array(ref mut items), segment) => {
+                let index = match *segment {
+                    PathSegment::Key(ref key) => key.to_index()?,
+                    PathSegment::Index(index) => index,
+                };
+                Arc::make_mut(items).get_mut(index)
+            }
+            _ => None,
+        })
+    }
+}
+
+impl Value {
+    /// Returns the value at a JSON pointer as defined by RFC 6901.
+    ///
+    /// The pointer is either empty, selecting this value, or a sequence of
+    /// segments each starting with `/`. Within a segment `~1` stands for `/`
+    /// and `~0` for `~`. Segments select string keys of tables, and when
+    /// written as a decimal number without leading zeros, also integer keys
+    /// of tables and elements of arrays.
+    pub fn pointer(&self, ptr: &str) -> Option<&Value> {
+        if ptr.is_empty() {
+            return Some(self);
+        }
+        if !ptr.starts_with('/') {
+            return None;
+        }
+        let mut val = self;
+        for segment in ptr[1..].split('/') {
+            val = lookup(val, &unescape_pointer(segment))?;
+        }
+        Some(val)
+    }
+
+    /// Mutably borrows the value at a JSON pointer, see `pointer`.
+    ///
+    /// Shared tables and arrays along the path are cloned first, but only
+    /// if the whole path exists.
+    pub fn pointer_mut(&mut self, ptr: &str) -> Option<&mut Value> {
+        // Check first, so that shared tables are not cloned for nothing.
+        self.pointer(ptr)?;
+        if ptr.is_empty() {
+            return Some(self);
+        }
+        if !ptr.starts_with('/') {
+            return None;
+        }
+        let mut val = self;
+        for segment in ptr[1..].split('/') {
+            val = lookup_mut(val, &unescape_pointer(segment))?;
+        }
+        Some(val)
+    }
+}
+
+impl Table {
+    /// Returns the value at a dotted path such as `"player.pos.x"`.
+    ///
+    /// Each segment looks up a string key in a nested table. A segment
+    /// that is a decimal number also finds an integer key, or an element
+    /// of an array, so `"verts.0"` works. Returns `None` if a segment is
+    /// missing or the path runs into a value that is not a container.
+    ///
+    /// Keys that contain `.` can not be reached this way,
+    /// use `get_path_segments` instead.
+    pub fn get_path(&self, path: &str) -> Option<&Value> {
+        get_segments(self, path.split('.'))
+    }
+
+    /// Returns the value at a path given as separate segments.
+    ///
+    /// This works like `get_path`, but segments may contain any character.
+    pub fn get_path_segments(&self, segments: &[&str]) -> Option<&Value> {
+        get_segments(self, segments.iter().cloned())
+    }
+
+    /// Mutably borrows the value at a dotted path.
+    ///
+    /// Segments are resolved like in `get_path`. Shared tables and arrays
+    /// along the path are cloned before being borrowed, but only if the
+    /// whole path exists.
+    pub fn get_path_mut(&mut self, path: &str) -> Option<&mut Value> {
+        // Check first, so that shared tables are not cloned for nothing.
+        self.get_path(path)?;
+        let mut segments = path.split('.');
+        let mut val = lookup_table_mut(self, segments.next()?)?;
+        for segment in segments {
+            val = lookup_mut(val, segment)?;
+        }
+        Some(val)
+    }
+
+    /// Returns whether a value exists at a dotted path.
+    ///
+    /// Segments are resolved like in `get_path`, so a missing segment or a
+    /// path running into a value that is not a container gives `false`.
+    pub fn contains_path(&self, path: &str) -> bool {
+        self.get_path(path).is_some()
+    }
+
+    /// Removes and returns the value at a dotted path.
+    ///
+    /// Segments are resolved like in `get_path`. The last segment removes
+    /// an entry of a table or an element of an array, which shifts the
+    /// elements after it. Tables and arrays that become empty are kept.
+    /// Returns `None` and changes nothing if the path does not exist.
+    pub fn remove_path(&mut self, path: &str) -> Option<Value> {
+        self.remove_path_impl(path, false)
+    }
+
+    /// Works like `remove_path`, but also removes the tables and arrays
+    /// along the path that become empty.
+    ///
+    /// Only the ancestors of the removed value are pruned, empty
+    /// containers elsewhere are kept. This table itself is never removed.
+    pub fn remove_path_and_prune(&mut self, path: &str) -> Option<Value> {
+        self.remove_path_impl(path, true)
+    }
+
+    fn remove_path_impl(&mut self, path: &str, prune: bool) -> Option<Value> {
+        // Check first, so that shared tables are not cloned for nothing.
+        if !self.contains_path(path) {
+            return None;
+        }
+        let segments: Vec<&str> = path.split('.').collect();
+        remove_in_table(self, &segments, prune)
+    }
+
+    /// Sets the value at a dotted path, creating missing tables on the way.
+    ///
+    /// Segments are resolved like in `get_path`, and missing keys are
+    /// inserted as strings. Returns an error without changing anything if
+    /// a segment before the last holds a value that is not a table.
+    pub fn set_path(&mut self, path: &str, value: Value) -> Result<(), PathError> {
+        let segments: Vec<&str> = path.split('.').collect();
+        let (last, parents) = segments.split_last().unwrap();
+        let mut table = self;
+        for (i, segment) in parents.iter().enumerate() {
+            if let Some(val) = lookup_table(table, segment) {
+                if !val.is_table() {
+                    return Err(PathError { segment: i, found: val.kind() });
+                }
+            }
+            let key = key_for(table, segment);
+            let val = table.entry(key)
+                .or_insert_with(|| Value::table(Table::new()));
+            table = match *val {
+                Value::Table(ref mut table) => Arc::make_mut(table),
+                _ => unreachable!(),
+            };
+        }
+        let key = key_for(table, last);
+        table.insert(key, value);
+        Ok(())
+    }
+}
+
+/// Parses a decimal index without sign or leading zeros.
+pub fn parse_index(segment: &str) -> Option<usize> {
+    let digits = segment.bytes().all(|b| b.is_ascii_digit());
+    if !digits || segment.is_empty() || (segment.len() > 1 && segment.starts_with('0')) {
+        None
+    } else {
+        segment.parse().ok()
+    }
+}
+
+/// Decodes `~1` to `/` and `~0` to `~` in a JSON pointer segment.
+fn unescape_pointer(segment: &str) -> String {
+    segment.replace("~1", "/").replace("~0", "~")
+}
+
+/// Returns the existing key matching a segment, or a new string key.
+fn key_for(table: &Table, segment: &str) -> Value {
+    let key = Value::str(segment);
+    if !table.contains_key(&key) {
+        if let Some(index) = parse_index(segment) {
+            if table.get_usize(index).is_some() {
+                return Value::Usize(index);
+            }
+        }
+    }
+    key
+}
+
+fn get_segments<'a, 'b, I>(table: &'a Table, mut segments: I) -> Option<&'a Value>
+    where I: Iterator<Item = &'b str>
+{
+    let mut val = lookup_table(table, segments.next()?)?;
+    for segment in segments {
+        val = lookup(val, segment)?;
+    }
+    Some(val)
+}
+
+fn lookup_table<'a>(table: &'a Table, segment: &str) -> Option<&'a Value> {
+    table.get_str(segment).or_else(|| {
+        parse_index(segment).and_then(|index| table.get_usize(index))
+    })
+}
+
+/// Looks up a segment in a table or array value.
+pub fn lookup<'a>(val: &'a Value, segment: &str) -> Option<&'a Value> {
+    match *val {
+        Value::Table(ref table) => lookup_table(table, segment),
+        Value::Array(ref items) => {
+            parse_index(segment).and_then(|index| items.get(index))
+        }
+        _ => None,
+    }
+}
+
+fn lookup_table_mut<'a>(
+    table: &'a mut Table,
+    segment: &str
+) -> Option<&'a mut Value> {
+    let key = key_for(table, segment);
+    table.get_mut(&key)
+}
+
+fn lookup_mut<'a>(val: &'a mut Value, segment: &str) -> Option<&'a mut Value> {
+    match *val {
+        Value::Table(ref mut table) => {
+            lookup_table_mut(Arc::make_mut(table), segment)
+        }
+        Value::Array(ref mut items) => {
+            let index = parse_index(segment)?;
+            Arc::make_mut(items).get_mut(index)
+        }
+        _ => None,
+    }
+}
+
+fn remove_in_table(table: &mut Table, segments: &[&str], prune: bool) -> Option<Value> {
+    let key = key_for(table, segments[0]);
+    if segments.len() == 1 {
+        return table.remove(&key);
+    }
+    let (removed, empty) = {
+        let val = table.get_mut(&key)?;
+        let removed = remove_in(val, &segments[1..], prune)?;
+        (removed, val.is_empty() == Some(true))
+    };
+    if prune && empty {
+        table.remove(&key);
+    }
+    Some(removed)
+}
+
+fn remove_in(val: &mut Value, segments: &[&str], prune: bool) -> Option<Value> {
+    match *val {
+        Value::Table(ref mut table) => {
+            remove_in_table(Arc::make_mut(table), segments, prune)
+        }
+        Value::Array(ref mut items) => {
+            let index = parse_index(segments[0])?;
+            let items = Arc::make_mut(items);
+            if segments.len() == 1 {
+                return if index < items.len() { Some(items.remove(index)) } else { None };
+            }
+            let (removed, empty) = {
+                let item = items.get_mut(index)?;
+                let removed = remove_in(item, &segments[1..], prune)?;
+                (removed, item.is_empty() == Some(true))
+            };
+            if prune && empty {
+                items.remove(index);
+            }
+            Some(removed)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use { Table, Value };
+
+    /// Returns `true` if both values are the same shared table.
+    fn shared(a: &Value, b: &Value) -> bool {
+        match (a, b) {
+            (&Value::Table(ref a), &Value::Table(ref b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    fn player() -> Table {
+        table!{
+            "player" => table!{
+                "pos" => table!{ "x" => 1.0, "y" => 2.0 },
+                "name" => "Sven",
+            },
+            "verts" => table!{ 0usize => "a", 1usize => "b" },
+            "list" => vec![Value::Null, Value::Bool(true)],
+            "a.b" => 3usize,
+        }
+    }
+
+    #[test]
+    fn test_get_path() {
+        let a = player();
+        assert_eq!(a.get_path("player.pos.x"), Some(&Value::f64(1.0)));
+        assert_eq!(a.get_path("player.name"), Some(&Value::str("Sven")));
+        assert_eq!(a.get_path("verts.1"), Some(&Value::str("b")));
+        assert_eq!(a.get_path("list.1"), Some(&Value::Bool(true)));
+        assert_eq!(a.get_path("player.pos.z"), None);
+        assert_eq!(a.get_path("player.name.first"), None);
+        assert_eq!(a.get_path("list.2"), None);
+        assert_eq!(a.get_path("a.b"), None);
+        assert_eq!(a.get_path_segments(&["a.b"]), Some(&Value::Usize(3)));
+        assert_eq!(a.get_path_segments(&["player", "pos", "y"]),
+            Some(&Value::f64(2.0)));
+        assert_eq!(a.get_path_segments(&[]), None);
+    }
+
+    #[test]
+    fn test_get_in() {
+        use PathSegment::{ Index, Key };
+
+        let a = Value::table(player());
+        let shared = a.clone();
+        let path = [Key("player".into()), Key("pos".into()), Key("x".into())];
+        assert_eq!(a.get_in(&path), Some(&Value::f64(1.0)));
+        assert_eq!(a.get_in(&[Key("a.b".into())]), Some(&Value::Usize(3)));
+        assert_eq!(a.get_in(&[Key("verts".into()), Index(1)]), Some(&Value::str("b")));
+        assert_eq!(a.get_in(&[Key("list".into()), Index(1)]), Some(&Value::Bool(true)));
+        assert_eq!(a.get_in(&[Key("list".into()), Key(Value::U64(1))]),
+            Some(&Value::Bool(true)));
+        assert_eq!(a.get_in(&[]), Some(&a));
+        assert_eq!(a.get_in(&[Key("list".into()), Index(2)]), None);
+        assert_eq!(a.get_in(&[Key("a".into()), Key("b".into())]), None);
+
+        let mut b = a.clone();
+        *b.get_in_mut(&[Key("list".into()), Index(0)]).unwrap() = Value::Usize(5);
+        *b.get_in_mut(&[Key("verts".into()), Index(0)]).unwrap() = Value::str("c");
+        assert_eq!(b.get_in(&[Key("list".into()), Index(0)]), Some(&Value::Usize(5)));
+        assert_eq!(b.get_in(&[Key("verts".into()), Index(0)]), Some(&Value::str("c")));
+        assert_eq!(b.get_in_mut(&[Key("missing".into())]), None);
+        assert_eq!(a, shared);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_set_path() {
+        use { PathError, ValueKind };
+
+        let mut a = player();
+        let before = a.clone();
+        a.set_path("player.pos.z", Value::f64(3.0)).unwrap();
+        a.set_path("x.y.z", Value::Usize(1)).unwrap();
+        a.set_path("verts.0", Value::str("c")).unwrap();
+        assert_eq!(a.get_path("player.pos.z"), Some(&Value::f64(3.0)));
+        assert_eq!(a.get_path("x.y.z"), Some(&Value::Usize(1)));
+        assert_eq!(a.get_path("verts.0"), Some(&Value::str("c")));
+        assert_eq!(a.get_path("verts").unwrap().as_table().unwrap().len(), 2);
+        assert_eq!(before.get_path("player.pos.z"), None);
+
+        assert_eq!(a.set_path("player.name.first", Value::Null),
+            Err(PathError { segment: 1, found: ValueKind::String }));
+        assert_eq!(a.get_path("player.name"), Some(&Value::str("Sven")));
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut a = player();
+        let before = a.clone();
+        assert!(a.contains_path("player.pos.x"));
+        assert!(a.contains_path("list.1"));
+        assert!(!a.contains_path("player.pos.z"));
+        assert!(!a.contains_path("player.name.first"));
+
+        assert_eq!(a.remove_path("player.pos.x"), Some(Value::f64(1.0)));
+        assert_eq!(a.remove_path("player.pos.x"), None);
+        assert_eq!(a.remove_path("player.name.first"), None);
+        assert_eq!(a.remove_path("nothing.here"), None);
+        assert_eq!(a.remove_path("verts.0"), Some(Value::str("a")));
+        assert_eq!(a.remove_path("list.0"), Some(Value::Null));
+        assert_eq!(a.get_path("list.0"), Some(&Value::Bool(true)));
+        assert_eq!(a.remove_path("player.pos.y"), Some(Value::f64(2.0)));
+        assert_eq!(a.get_path("player.pos"), Some(&Value::table(Table::new())));
+        assert!(before.contains_path("player.pos.x"));
+    }
+
+    #[test]
+    fn test_remove_path_and_prune() {
+        let mut a = table!{
+            "a" => table!{ "b" => table!{ "c" => 1usize }, "d" => Table::new() },
+            "list" => vec![Value::table(table!{ "x" => 2usize })],
+        };
+        assert_eq!(a.remove_path_and_prune("a.b.c"), Some(Value::Usize(1)));
+        assert_eq!(a.get_path("a"), Some(&Value::table(table!{ "d" => Table::new() })));
+        assert_eq!(a.remove_path_and_prune("list.0.x"), Some(Value::Usize(2)));
+        assert!(!a.contains_path("list"));
+        assert_eq!(a.remove_path_and_prune("a.d"), Some(Value::table(Table::new())));
+        assert_eq!(a, Table::new());
+        assert_eq!(a.remove_path_and_prune("a"), None);
+    }
+
+    #[test]
+    fn test_pointer() {
+        // The examples from RFC 6901, section 5.
+        let doc = Value::from_json_str(r#"{
+            "foo": ["bar", "baz"],
+            "": 0,
+            "a/b": 1,
+            "c%d": 2,
+            "e^f": 3,
+            "g|h": 4,
+            "i\\j": 5,
+            "k\"l": 6,
+            " ": 7,
+            "m~n": 8
+        }"#).unwrap();
+        assert_eq!(doc.pointer(""), Some(&doc));
+        assert_eq!(doc.pointer("/foo"), doc.as_table().unwrap().get_str("foo"));
+        assert_eq!(doc.pointer("/foo/0"), Some(&Value::str("bar")));
+        assert_eq!(doc.pointer("/"), Some(&Value::Usize(0)));
+        assert_eq!(doc.pointer("/a~1b"), Some(&Value::Usize(1)));
+        assert_eq!(doc.pointer("/c%d"), Some(&Value::Usize(2)));
+        assert_eq!(doc.pointer("/e^f"), Some(&Value::Usize(3)));
+        assert_eq!(doc.pointer("/g|h"), Some(&Value::Usize(4)));
+        assert_eq!(doc.pointer("/i\\j"), Some(&Value::Usize(5)));
+        assert_eq!(doc.pointer("/k\"l"), Some(&Value::Usize(6)));
+        assert_eq!(doc.pointer("/ "), Some(&Value::Usize(7)));
+        assert_eq!(doc.pointer("/m~0n"), Some(&Value::Usize(8)));
+
+        assert_eq!(doc.pointer("foo"), None);
+        assert_eq!(doc.pointer("/foo/01"), None);
+        assert_eq!(doc.pointer("/foo/2"), None);
+        let list = Value::array(vec![Value::str("a")]);
+        assert_eq!(list.pointer("/0"), Some(&Value::str("a")));
+        assert_eq!(list.pointer("/-"), None);
+    }
+
+    #[test]
+    fn test_pointer_mut() {
+        let mut doc = Value::table(player());
+        let before = doc.clone();
+        *doc.pointer_mut("/player/pos/x").unwrap() = Value::f64(9.0);
+        *doc.pointer_mut("/list/1").unwrap() = Value::Null;
+        assert_eq!(doc.pointer("/player/pos/x"), Some(&Value::f64(9.0)));
+        assert_eq!(doc.pointer("/list/1"), Some(&Value::Null));
+        assert_eq!(before.pointer("/list/1"), Some(&Value::Bool(true)));
+        assert!(doc.pointer_mut("/player/missing").is_none());
+
+        let before = doc.clone();
+        let player = |val: &Value| val.pointer("/player").unwrap().clone();
+        assert!(doc.pointer_mut("/player/pos/z").is_none());
+        assert!(shared(&player(&doc), &player(&before)));
+        doc.pointer_mut("/player/pos/x").unwrap();
+        assert!(!shared(&player(&doc), &player(&before)));
+    }
+
+    #[test]
+    fn test_get_path_mut() {
+        let mut a = player();
+        let before = a.clone();
+        *a.get_path_mut("player.pos.x").unwrap() = Value::f64(5.0);
+        *a.get_path_mut("list.0").unwrap() = Value::Usize(0);
+        assert_eq!(a.get_path("player.pos.x"), Some(&Value::f64(5.0)));
+        assert_eq!(a.get_path("list.0"), Some(&Value::Usize(0)));
+        assert_eq!(before.get_path("player.pos.x"), Some(&Value::f64(1.0)));
+        assert!(a.get_path_mut("player.missing").is_none());
+        assert!(a.get_path_mut("list.5").is_none());
+
+        let before = a.clone();
+        assert!(a.get_path_mut("player.pos.z").is_none());
+        assert!(shared(&a["player"], &before["player"]));
+        a.get_path_mut("player.pos.x").unwrap();
+        assert!(!shared(&a["player"], &before["player"]));
+    }
+}
@@ -0,0 +1,110 @@
+//! Interning of repeated strings.
+
+use std::collections::HashSet;
+
+use { Str, Value };
+
+/// A set of shared strings for deduplicating repeated keys.
+///
+/// Every `Value::str` call allocates a new string, unless it is short
+/// enough to be stored inline. When loading many records with the same
+/// keys, interning them through a pool makes all equal long strings share
+/// one allocation. Short strings are returned without being stored. The
+/// pool is `Send` and `Sync`, so it can be shared between threads behind
+/// an `Arc<Mutex<StringPool>>`.
+#[derive(Clone, Debug, Default)]
+pub struct StringPool {
+    strings: HashSet<Str>,
+}
+
+impl StringPool {
+    /// Creates an empty pool.
+    pub fn new() -> StringPool {
+        StringPool { strings: HashSet::new() }
+    }
+
+    /// Returns a string value, sharing the string with earlier calls.
+    pub fn intern(&mut self, text: &str) -> Value {
+        if let Some(interned) = self.strings.get(text) {
+            return Value::String(interned.clone());
+        }
+        let text = Str::new(text);
+        if !text.is_inline() {
+            self.strings.insert(text.clone());
+        }
+        Value::String(text)
+    }
+
+    /// Returns the number of distinct strings stored in the pool.
+    pub fn len(&self) -> usize {
+        self.strings.len()
+    }
+
+    /// Returns `true` if the pool holds no strings.
+    pub fn is_empty(&self) -> bool {
+        self.strings.is_empty()
+    }
+
+    /// Removes all strings from the pool.
+    ///
+    /// Values returned earlier keep their strings.
+    pub fn clear(&mut self) {
+        self.strings.clear()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate test;
+
+    use super::*;
+    use self::test::Bencher;
+    use Table;
+
+    const KEYS: [&str; 4] = [
+        "player_name_column", "player_level_column",
+        "position_x_column", "position_y_column",
+    ];
+
+    #[test]
+    fn test_intern() {
+        let mut pool = StringPool::new();
+        assert!(pool.is_empty());
+        let a = pool.intern(KEYS[0]);
+        let b = pool.intern(KEYS[0]);
+        let c = pool.intern(KEYS[1]);
+        assert_eq!(a, Value::str(KEYS[0]));
+        assert_eq!(pool.len(), 2);
+        match (&a, &b, &c) {
+            (&Value::String(ref a), &Value::String(ref b), &Value::String(ref c)) => {
+                assert!(Str::ptr_eq(a, b));
+                assert!(!Str::ptr_eq(a, c));
+            }
+            _ => panic!("expected strings"),
+        }
+        assert_eq!(pool.intern("x"), Value::str("x"));
+        assert_eq!(pool.len(), 2);
+        pool.clear();
+        assert!(pool.is_empty());
+        assert_eq!(a, Value::str(KEYS[0]));
+    }
+
+    #[bench]
+    fn bench_records_naive(bencher: &mut Bencher) {
+        bencher.iter(|| {
+            (0..100).map(|i| {
+                KEYS.iter().map(|key| (Value::str(key), Value::Usize(i))).collect()
+            }).collect::<Vec<Table>>()
+        });
+    }
+
+    #[bench]
+    fn bench_records_interned(bencher: &mut Bencher) {
+        bencher.iter(|| {
+            let mut pool = StringPool::new();
+            (0..100).map(|i| {
+                KEYS.iter().map(|key| (pool.intern(key), Value::Usize(i))).collect()
+            }).collect::<Vec<Table>>()
+        });
+    }
+}
@@ -0,0 +1,119 @@
+//! Template strings filled from tables.
+
+use std::error::Error;
+use std::fmt::{ self, Write };
+
+use Table;
+
+/// An error returned when rendering a template fails.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum RenderError {
+    /// A placeholder names a path that does not exist.
+    Missing(String),
+    /// The brace at this byte offset is neither closed nor escaped.
+    UnmatchedBrace(usize),
+}
+
+impl fmt::Display for RenderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RenderError::Missing(ref path) => write!(f, "missing value for {{{}}}", path),
+            RenderError::UnmatchedBrace(offset) => {
+                write!(f, "unmatched brace at byte {}", offset)
+            }
+        }
+    }
+}
+
+impl Error for RenderError {}
+
+impl Table {
+    /// Replaces `{path}` placeholders in a template with values.
+    ///
+    /// Paths are looked up like in `get_path`, so `{a.b.c}` reaches into
+    /// nested tables, and values are written in their `Display` form.
+    /// Write `{{` and `}}` for literal braces. Returns an error for missing
+    /// values and for braces that are neither closed nor escaped.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// # fn main() {
+    /// let player = table!{ "name" => "Sven", "pos" => table!{ "x" => 1.5 } };
+    /// assert_eq!(player.render("{name} at {{{pos.x}}}").unwrap(), "Sven at {1.5}");
+    /// # }
+    /// ```
+    pub fn render(&self, template: &str) -> Result<String, RenderError> {
+        render(self, template, false)
+    }
+
+    /// Works like `render`, but never fails.
+    ///
+    /// Placeholders with missing values are left blank and unmatched
+    /// braces are kept as they are.
+    pub fn render_lossy(&self, template: &str) -> String {
+        render(self, template, true).unwrap()
+    }
+}
+
+fn render(table: &Table, template: &str, lossy: bool) -> Result<String, RenderError> {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(i) = rest.find(['{', '}']) {
+        let offset = template.len() - rest.len() + i;
+        out.push_str(&rest[..i]);
+        let brace = &rest[i..i + 1];
+        rest = &rest[i + 1..];
+        if rest.starts_with(brace) {
+            out.push_str(brace);
+            rest = &rest[1..];
+            continue;
+        }
+        let end = if brace == "{" { rest.find('}') } else { None };
+        match end {
+            Some(end) => {
+                let path = &rest[..end];
+                match table.get_path(path) {
+                    Some(val) => write!(out, "{}", val).unwrap(),
+                    None if lossy => {}
+                    None => return Err(RenderError::Missing(path.into())),
+                }
+                rest = &rest[end + 1..];
+            }
+            None if lossy => out.push_str(brace),
+            None => return Err(RenderError::UnmatchedBrace(offset)),
+        }
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Value;
+
+    #[test]
+    fn test_render() {
+        let a = table!{
+            "name" => "Sven",
+            "pos" => table!{ "x" => 1.5, "tags" => vec![Value::str("a")] },
+            "items" => table!{ 0 => 7 },
+        };
+        assert_eq!(a.render("{name}: {pos.x}, {items.0}"), Ok("Sven: 1.5, 7".into()));
+        assert_eq!(a.render("{{name}} }}{{"), Ok("{name} }{".into()));
+        assert_eq!(a.render("{pos.tags}"), Ok("[\"a\"]".into()));
+        assert_eq!(a.render(""), Ok("".into()));
+        assert_eq!(a.render("{nick}"), Err(RenderError::Missing("nick".into())));
+        assert_eq!(a.render("ab{name"), Err(RenderError::UnmatchedBrace(2)));
+        assert_eq!(a.render("a}b"), Err(RenderError::UnmatchedBrace(1)));
+    }
+
+    #[test]
+    fn test_render_lossy() {
+        let a = table!{ "name" => "Sven" };
+        assert_eq!(a.render_lossy("[{nick}] {name} {x"), "[] Sven {x");
+        assert_eq!(a.render_lossy("} {{"), "} {");
+    }
+}
@@ -0,0 +1,512 @@
+//! Reading and writing values as RON, enabled by the `ron` feature.
+//!
+//! RON is the Rusty Object Notation. This module implements the subset
+//! that values need, so it has no dependency.
+
+use std::fmt::{ self, Write };
+use std::sync::Arc;
+
+use json::{ ParseError, MAX_DEPTH };
+use { Table, Value };
+
+impl Value {
+    /// Writes the value as compact RON.
+    ///
+    /// `Null` is written as `()`, chars as `'c'`, bytes as byte strings
+    /// like `b"ab"`, arrays as lists and tables as maps with entries
+    /// sorted by key. Floats always have a decimal point or are `inf`,
+    /// `-inf` or `NaN`, so they read back as floats.
+    pub fn to_ron_string(&self) -> String {
+        let mut out = String::new();
+        write_ron(&mut out, self).unwrap();
+        out
+    }
+
+    /// Parses a value from RON.
+    ///
+    /// Maps become tables with keys of any kind and lists become arrays.
+    /// `None` becomes `Null`, and `Some(x)` becomes `x`. Integers become
+    /// `Usize` when non-negative and `I64` when negative, falling back to
+    /// `U64` when out of range. Line and block comments are skipped and
+    /// trailing commas are allowed. Numbers must be decimal. Structs,
+    /// tuples and raw strings are not supported.
+    ///
+    /// Lists, maps and `Some` may be nested at most 128 levels deep, like
+    /// in `from_json_str`.
+    pub fn from_ron_str(text: &str) -> Result<Value, ParseError> {
+        let mut parser = Parser { text, pos: 0, depth: 0 };
+        let val = parser.value()?;
+        parser.skip_whitespace()?;
+        if parser.pos < text.len() {
+            return Err(parser.error("trailing characters"));
+        }
+        Ok(val)
+    }
+}
+
+fn write_ron<W: Write>(w: &mut W, val: &Value) -> fmt::Result {
+    match *val {
+        Value::Null => w.write_str("()"),
+        Value::Bool(x) => write!(w, "{}", x),
+        Value::Usize(x) => write!(w, "{}", x),
+        Value::U64(x) => write!(w, "{}", x),
+        Value::I64(x) => write!(w, "{}", x),
+        Value::F64(x) => {
+            if x.0.is_nan() {
+                w.write_str("NaN")
+            } else if x.0.is_infinite() {
+                w.write_str(if x.0 > 0.0 { "inf" } else { "-inf" })
+            } else {
+                write!(w, "{:?}", x.0)
+            }
+        }
+        Value::Char(ch) => {
+            w.write_char('\'')?;
+            write_escaped(w, ch, '\'')?;
+            w.write_char('\'')
+        }
+        Value::String(ref text) => {
+            w.write_char('"')?;
+            for ch in text.chars() {
+                write_escaped(w, ch, '"')?;
+            }
+            w.write_char('"')
+        }
+        Value::Bytes(ref bytes) => {
+            w.write_str("b\"")?;
+            for &b in bytes.iter() {
+                match b {
+                    b'"' => w.write_str("\\\"")?,
+                    b'\\' => w.write_str("\\\\")?,
+                    b' '..=b'~' => w.write_char(b as char)?,
+                    _ => write!(w, "\\x{:02x}", b)?,
+                }
+            }
+            w.write_char('"')
+        }
+        Value::Array(ref items) => {
+            w.write_char('[')?;
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 { w.write_str(", ")?; }
+                write_ron(w, item)?;
+            }
+            w.write_char(']')
+        }
+        Value::Table(ref table) => {
+            w.write_char('{')?;
+            for (i, (key, val)) in table.sorted_entries().into_iter().enumerate() {
+                if i > 0 { w.write_str(", ")?; }
+                write_ron(w, key)?;
+                w.write_str(": ")?;
+                write_ron(w, val)?;
+            }
+            w.write_char('}')
+        }
+    }
+}
+
+fn write_escaped<W: Write>(w: &mut W, ch: char, quote: char) -> fmt::Result {
+    match ch {
+        '\\' => w.write_str("\\\\"),
+        '\n' => w.write_str("\\n"),
+        '\r' => w.write_str("\\r"),
+        '\t' => w.write_str("\\t"),
+        '\0' => w.write_str("\\0"),
+        ch if ch == quote => write!(w, "\\{}", ch),
+        ch if ch.is_control() => write!(w, "\\u{{{:x}}}", ch as u32),
+        ch => w.write_char(ch),
+    }
+}
+
+/// Returns `true` if the text is only hex digits, since `from_str_radix`
+/// also accepts a leading `+`.
+fn is_hex(text: &str) -> bool {
+    text.bytes().all(|b| b.is_ascii_hexdigit())
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// The number of lists, maps and `Some` being parsed.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { offset: self.pos, message: message.into() }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn eat(&mut self, literal: &str) -> bool {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), ParseError> {
+        loop {
+            let trimmed = self.rest().trim_start();
+            self.pos = self.text.len() - trimmed.len();
+            if self.eat("//") {
+                let line = self.rest().find('\n').unwrap_or(self.rest().len());
+                self.pos += line;
+            } else if self.eat("/*") {
+                match self.rest().find("*/") {
+                    Some(i) => self.pos += i + 2,
+                    None => return Err(self.error("unterminated comment")),
+                }
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.skip_whitespace()?;
+        let word: String = self.rest().chars()
+            .take_while(|&ch| ch.is_ascii_alphanumeric() || ch == '_')
+            .collect();
+        match &*word {
+            "true" | "false" | "None" | "inf" | "NaN" => {
+                self.pos += word.len();
+                return Ok(match &*word {
+                    "true" => Value::Bool(true),
+                    "false" => Value::Bool(false),
+                    "None" => Value::Null,
+                    "inf" => Value::f64(f64::INFINITY),
+                    _ => Value::f64(f64::NAN),
+                });
+            }
+            "Some" => {
+                self.pos += word.len();
+                self.skip_whitespace()?;
+                if !self.eat("(") {
+                    return Err(self.error("expected `(`"));
+                }
+                return self.nested(|parser| {
+                    let val = parser.value()?;
+                    parser.skip_whitespace()?;
+                    if !parser.eat(")") {
+                        return Err(parser.error("expected `)`"));
+                    }
+                    Ok(val)
+                });
+            }
+            _ => {}
+        }
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some('(') => {
+                self.pos += 1;
+                self.skip_whitespace()?;
+                if !self.eat(")") {
+                    return Err(self.error("expected `)`"));
+                }
+                Ok(Value::Null)
+            }
+            Some('\'') => self.char_literal(),
+            Some('"') => {
+                let text = self.quoted(false)?;
+                // Only whole characters were pushed, so this is valid UTF-8.
+                Ok(Value::from(String::from_utf8(text).unwrap()))
+            }
+            Some('b') if self.rest().starts_with("b\"") => {
+                self.pos += 1;
+                self.quoted(true).map(Value::from)
+            }
+            Some('[') => self.nested(Parser::list),
+            Some('{') => self.nested(Parser::map),
+            Some('-') | Some('+') | Some('0'..='9') => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    /// Parses a list, map or the inside of `Some` one level deeper, see
+    /// `MAX_DEPTH`.
+    fn nested<F>(&mut self, parse: F) -> Result<Value, ParseError>
+        where F: FnOnce(&mut Parser<'a>) -> Result<Value, ParseError>
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let val = parse(self);
+        self.depth -= 1;
+        val
+    }
+
+    /// Parses the elements of a list or the entries of a map, up to and
+    /// including the closing bracket.
+    fn items<F>(&mut self, close: &str, mut item: F) -> Result<(), ParseError>
+        where F: FnMut(&mut Parser<'a>) -> Result<(), ParseError>
+    {
+        self.pos += 1;
+        loop {
+            self.skip_whitespace()?;
+            if self.eat(close) {
+                return Ok(());
+            }
+            item(self)?;
+            self.skip_whitespace()?;
+            if !self.eat(",") {
+                self.skip_whitespace()?;
+                if self.eat(close) {
+                    return Ok(());
+                }
+                return Err(self.error(&format!("expected `,` or `{}`", close)));
+            }
+        }
+    }
+
+    fn list(&mut self) -> Result<Value, ParseError> {
+        let mut items = vec![];
+        self.items("]", |parser| {
+            items.push(parser.value()?);
+            Ok(())
+        })?;
+        Ok(Value::Array(Arc::new(items)))
+    }
+
+    fn map(&mut self) -> Result<Value, ParseError> {
+        let mut table = Table::new();
+        self.items("}", |parser| {
+            let key = parser.value()?;
+            parser.skip_whitespace()?;
+            if !parser.eat(":") {
+                return Err(parser.error("expected `:`"));
+            }
+            let val = parser.value()?;
+            table.insert(key, val);
+            Ok(())
+        })?;
+        Ok(Value::table(table))
+    }
+
+    /// Parses an escape after a backslash, pushing it as UTF-8, or as a
+    /// single byte for `\x` escapes in byte strings.
+    fn escape(&mut self, out: &mut Vec<u8>, bytes: bool) -> Result<(), ParseError> {
+        let start = self.pos;
+        let ch = match self.next_char() {
+            Some('\\') => '\\',
+            Some('"') => '"',
+            Some('\'') => '\'',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some('0') => '\0',
+            Some('x') => {
+                let code = self.rest().get(..2)
+                    .filter(|&digits| is_hex(digits))
+                    .and_then(|digits| u8::from_str_radix(digits, 16).ok())
+                    .filter(|&code| bytes || code < 0x80);
+                match code {
+                    Some(code) => {
+                        self.pos += 2;
+                        out.push(code);
+                        return Ok(());
+                    }
+                    None => return Err(self.error("invalid byte escape")),
+                }
+            }
+            Some('u') if self.eat("{") => {
+                let end = self.rest().find('}');
+                let code = end
+                    .map(|end| &self.rest()[..end])
+                    .filter(|&digits| is_hex(digits))
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(::std::char::from_u32);
+                match (end, code) {
+                    (Some(end), Some(ch)) => {
+                        self.pos += end + 1;
+                        ch
+                    }
+                    _ => return Err(self.error("invalid unicode escape")),
+                }
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error("invalid escape"));
+            }
+        };
+        out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes());
+        Ok(())
+    }
+
+    fn char_literal(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1;
+        let start = self.pos;
+        let mut buf = vec![];
+        match self.next_char() {
+            Some('\\') => self.escape(&mut buf, false)?,
+            Some('\'') | None => return Err(self.error("invalid char literal")),
+            Some(_) => {}
+        }
+        if !self.eat("'") {
+            return Err(self.error("expected `'`"));
+        }
+        let ch = if buf.is_empty() {
+            self.text[start..].chars().next().unwrap()
+        } else {
+            // Escapes in char literals are pushed as UTF-8.
+            ::std::str::from_utf8(&buf).unwrap().chars().next().unwrap()
+        };
+        Ok(Value::Char(ch))
+    }
+
+    /// Parses the contents of a quoted string or byte string.
+    fn quoted(&mut self, bytes: bool) -> Result<Vec<u8>, ParseError> {
+        self.pos += 1;
+        let mut out = vec![];
+        loop {
+            match self.next_char() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') => return Ok(out),
+                Some('\\') => self.escape(&mut out, bytes)?,
+                Some(ch) if bytes && !ch.is_ascii() => {
+                    return Err(self.error("non-ASCII character in byte string"));
+                }
+                Some(ch) => out.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        if let Some('-') | Some('+') = self.peek() { self.pos += 1; }
+        if self.eat("inf") {
+            let negative = self.text[start..].starts_with('-');
+            let x = if negative { f64::NEG_INFINITY } else { f64::INFINITY };
+            return Ok(Value::f64(x));
+        }
+        let mut prev = ' ';
+        while let Some(ch) = self.peek() {
+            let sign = (ch == '-' || ch == '+') && (prev == 'e' || prev == 'E');
+            if !(ch.is_ascii_digit() || sign || ['_', '.', 'e', 'E'].contains(&ch)) {
+                break;
+            }
+            self.pos += 1;
+            prev = ch;
+        }
+        let text: String = self.text[start..self.pos].chars()
+            .filter(|&ch| ch != '_')
+            .collect();
+        let digits = text.trim_start_matches('+');
+        if !text.contains(['.', 'e', 'E']) {
+            if let Ok(x) = digits.parse::<usize>() { return Ok(Value::Usize(x)); }
+            if let Ok(x) = digits.parse::<u64>() { return Ok(Value::U64(x)); }
+            if let Ok(x) = digits.parse::<i64>() { return Ok(Value::I64(x)); }
+        }
+        match digits.parse::<f64>() {
+            Ok(x) => Ok(Value::f64(x)),
+            Err(_) => {
+                self.pos = start;
+                Err(self.error("invalid number"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use { Table, Value };
+
+    fn round_trip(val: &Value) -> Value {
+        Value::from_ron_str(&val.to_ron_string()).unwrap()
+    }
+
+    #[test]
+    fn test_scalars() {
+        let scalars = [
+            Value::Null,
+            Value::Bool(true),
+            Value::Usize(3),
+            Value::U64(u64::MAX),
+            Value::I64(-4),
+            Value::f64(2.5),
+            Value::f64(-1e300),
+            Value::f64(f64::INFINITY),
+            Value::f64(f64::NEG_INFINITY),
+            Value::f64(f64::NAN),
+            Value::Char('\''),
+            Value::Char('é'),
+            Value::str("a \"quoted\"\n\\ line\u{1}é"),
+            Value::from(&[0u8, b'"', b'a', 0xff][..]),
+        ];
+        for val in scalars.iter() {
+            let back = round_trip(val);
+            assert_eq!(back, *val, "{}", val.to_ron_string());
+            assert_eq!(back.is_number(), val.is_number());
+        }
+        assert_eq!(round_trip(&Value::f64(1.0)).kind(), ::ValueKind::F64);
+        assert_eq!(Value::Null.to_ron_string(), "()");
+        assert_eq!(Value::Char('x').to_ron_string(), "'x'");
+        assert_eq!(Value::from(&b"a\n"[..]).to_ron_string(), "b\"a\\x0a\"");
+    }
+
+    #[test]
+    fn test_nested() {
+        let a = Value::table(table!{
+            "name" => "Sven",
+            "pos" => table!{ 0usize => 1.5, 1usize => -2.0 },
+            "tags" => vec![Value::str("a"), Value::table(Table::new()), Value::Null],
+            Value::I64(-1) => table!{ Value::Bool(true) => 'c' },
+        });
+        assert_eq!(a.to_ron_string(), "{-1: {true: 'c'}, \"name\": \"Sven\", \
+            \"pos\": {0: 1.5, 1: -2.0}, \"tags\": [\"a\", {}, ()]}");
+        assert_eq!(round_trip(&a), a);
+    }
+
+    #[test]
+    fn test_parse() {
+        let text = r#"
+            // A fixture.
+            {
+                "a": Some(1_000), /* block
+                comment */ "b": None,
+                "c": [1e3, +2, -0.5, b"\x00\xff", "\u{e9}",],
+                'k': (),
+            }
+        "#;
+        let val = Value::from_ron_str(text).unwrap();
+        assert_eq!(val, Value::table(table!{
+            "a" => 1000usize,
+            "b" => Value::Null,
+            "c" => vec![Value::f64(1000.0), Value::Usize(2), Value::f64(-0.5),
+                Value::from(&[0u8, 0xff][..]), Value::str("é")],
+            'k' => Value::Null,
+        }));
+        for text in &["", "[1,,]", "{1 2}", "'ab'", "\"abc", "b\"é\"", "\"\\xff\"",
+                      "1 2", "/* x", "Some(1", "-x", "\"\\x+f\"", "\"\\u{+41}\"",
+                      "b\"\\x+f\""] {
+            assert!(Value::from_ron_str(text).is_err(), "{}", text);
+        }
+        assert_eq!(Value::from_ron_str("[1, ?]").unwrap_err().offset, 4);
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        for open in &["[", "{1:", "Some("] {
+            let err = Value::from_ron_str(&open.repeat(100_000)).unwrap_err();
+            assert_eq!(err.message, "nesting too deep", "{}", open);
+        }
+        let deepest = format!("{}{}", "Some([".repeat(64), "])".repeat(64));
+        assert_eq!(Value::from_ron_str(&deepest).unwrap().to_ron_string(),
+            format!("{}{}", "[".repeat(64), "]".repeat(64)));
+    }
+}
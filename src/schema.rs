@@ -0,0 +1,209 @@
+//! Validation of tables against a declarative schema.
+
+use std::error::Error;
+use std::fmt;
+
+use walk::PathSegment;
+use { Table, Value, ValueKind };
+
+/// Describes the keys a table is expected to have.
+///
+/// Each field names a key, the kind of its value and whether it must be
+/// present. Fields holding tables can have a nested schema. Keys that the
+/// schema does not mention are allowed.
+///
+/// ```
+/// use table::{ Schema, TableBuilder, ValueKind };
+///
+/// let schema = Schema::new()
+///     .required("name", ValueKind::String)
+///     .optional("level", ValueKind::U64)
+///     .required_table("pos", Schema::new()
+///         .required("x", ValueKind::F64)
+///         .required("y", ValueKind::F64));
+/// let player = TableBuilder::new()
+///     .set("name", "Sven")
+///     .set_table("pos", |b| b.set("x", 1.0).set("y", 2.0))
+///     .build();
+/// assert!(schema.validate(&player).is_ok());
+/// ```
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Schema {
+    fields: Vec<Field>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+struct Field {
+    key: Value,
+    kind: ValueKind,
+    required: bool,
+    nested: Option<Schema>,
+}
+
+/// An error describing one way a table does not match a schema.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ValidationError {
+    /// The path from the validated table to the offending value.
+    pub path: Vec<PathSegment>,
+    /// The kind the schema expects.
+    pub expected: ValueKind,
+    /// The kind that was found, or `None` if a required key is missing.
+    pub found: Option<ValueKind>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, segment) in self.path.iter().enumerate() {
+            if i > 0 { f.write_str(".")?; }
+            match *segment {
+                PathSegment::Key(ref key) => write!(f, "{}", key)?,
+                PathSegment::Index(index) => write!(f, "{}", index)?,
+            }
+        }
+        match self.found {
+            Some(found) => write!(f, ": expected {}, found {}", self.expected, found),
+            None => write!(f, ": missing {}", self.expected),
+        }
+    }
+}
+
+impl Error for ValidationError {}
+
+impl Schema {
+    /// Creates a schema without fields, which every table matches.
+    pub fn new() -> Schema {
+        Schema { fields: vec![] }
+    }
+
+    /// Adds a key that must be present with a value of the given kind.
+    pub fn required<K: Into<Value>>(self, key: K, kind: ValueKind) -> Schema {
+        self.field(key.into(), kind, true, None)
+    }
+
+    /// Adds a key that may be missing, but otherwise must have a value of
+    /// the given kind.
+    pub fn optional<K: Into<Value>>(self, key: K, kind: ValueKind) -> Schema {
+        self.field(key.into(), kind, false, None)
+    }
+
+    /// Adds a key that must hold a table matching a nested schema.
+    pub fn required_table<K: Into<Value>>(self, key: K, schema: Schema) -> Schema {
+        self.field(key.into(), ValueKind::Table, true, Some(schema))
+    }
+
+    /// Adds a key that may be missing, but otherwise must hold a table
+    /// matching a nested schema.
+    pub fn optional_table<K: Into<Value>>(self, key: K, schema: Schema) -> Schema {
+        self.field(key.into(), ValueKind::Table, false, Some(schema))
+    }
+
+    fn field(mut self, key: Value, kind: ValueKind, required: bool,
+             nested: Option<Schema>) -> Schema {
+        self.fields.retain(|field| field.key != key);
+        self.fields.push(Field { key, kind, required, nested });
+        self
+    }
+
+    /// Checks a table against the schema.
+    ///
+    /// All violations are collected, in the order the fields were added,
+    /// with nested errors following the error-free parent field. Kinds must
+    /// match exactly, so a `Usize` value does not satisfy a `U64` field.
+    pub fn validate(&self, table: &Table) -> Result<(), Vec<ValidationError>> {
+        let mut errors = vec![];
+        self.validate_into(table, &mut vec![], &mut errors);
+        if errors.is_empty() { Ok(()) } else { Err(errors) }
+    }
+
+    fn validate_into(&self, table: &Table, path: &mut Vec<PathSegment>,
+                     errors: &mut Vec<ValidationError>) {
+        for field in &self.fields {
+            path.push(PathSegment::Key(field.key.clone()));
+            let found = table.get(&field.key).map(Value::kind);
+            if found != Some(field.kind) && (found.is_some() || field.required) {
+                errors.push(ValidationError {
+                    path: path.clone(),
+                    expected: field.kind,
+                    found,
+                });
+            } else if let (Some(schema), Some(nested)) =
+                (&field.nested, table.get(&field.key).and_then(Value::as_table))
+            {
+                schema.validate_into(nested, path, errors);
+            }
+            path.pop();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema() -> Schema {
+        Schema::new()
+            .required("name", ValueKind::String)
+            .optional("level", ValueKind::U64)
+            .required_table("pos", Schema::new()
+                .required("x", ValueKind::F64)
+                .optional("y", ValueKind::F64))
+    }
+
+    fn path(keys: &[&str]) -> Vec<PathSegment> {
+        keys.iter().map(|key| PathSegment::Key(Value::str(key))).collect()
+    }
+
+    #[test]
+    fn test_validate() {
+        let valid = table!{
+            "name" => "Sven",
+            "pos" => table!{ "x" => 1.0 },
+            "extra" => 1,
+        };
+        assert_eq!(schema().validate(&valid), Ok(()));
+        let valid = table!{
+            "name" => "Sven",
+            "level" => 3u64,
+            "pos" => table!{ "x" => 1.0, "y" => 2.0 },
+        };
+        assert_eq!(schema().validate(&valid), Ok(()));
+        assert_eq!(Schema::new().validate(&Table::new()), Ok(()));
+    }
+
+    #[test]
+    fn test_validate_errors() {
+        let invalid = table!{ "level" => "high", "pos" => table!{ "y" => true } };
+        let errors = schema().validate(&invalid).unwrap_err();
+        assert_eq!(errors, vec![
+            ValidationError {
+                path: path(&["name"]),
+                expected: ValueKind::String,
+                found: None,
+            },
+            ValidationError {
+                path: path(&["level"]),
+                expected: ValueKind::U64,
+                found: Some(ValueKind::String),
+            },
+            ValidationError {
+                path: path(&["pos", "x"]),
+                expected: ValueKind::F64,
+                found: None,
+            },
+            ValidationError {
+                path: path(&["pos", "y"]),
+                expected: ValueKind::F64,
+                found: Some(ValueKind::Bool),
+            },
+        ]);
+        assert_eq!(errors[0].to_string(), "name: missing string");
+        assert_eq!(errors[3].to_string(), "pos.y: expected f64, found bool");
+
+        let invalid = table!{ "name" => "Sven", "pos" => 1.0 };
+        assert_eq!(schema().validate(&invalid).unwrap_err(), vec![ValidationError {
+            path: path(&["pos"]),
+            expected: ValueKind::Table,
+            found: Some(ValueKind::F64),
+        }]);
+    }
+}
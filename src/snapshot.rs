@@ -0,0 +1,162 @@
+//! Snapshots for rolling back changes to a table.
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use { Table, Value };
+
+/// A saved state of a table, see `Value::snapshot` and `Table::snapshot`.
+///
+/// The state is kept behind an `Arc`, so cloning a snapshot is O(1).
+#[derive(Clone, Debug)]
+pub struct TableSnapshot {
+    table: Arc<Table>,
+}
+
+impl Deref for TableSnapshot {
+    type Target = Table;
+
+    fn deref(&self) -> &Table {
+        &self.table
+    }
+}
+
+impl Value {
+    /// Saves the state of a table value in O(1), or returns `None` if
+    /// this is not a table.
+    ///
+    /// The snapshot shares the table through its `Arc`, so taking it only
+    /// bumps a reference count. The first change to the value afterwards,
+    /// for example through `as_table_mut`, copies the top level entries,
+    /// and nested data is copied only where it changes. Keep data that is
+    /// saved often in a `Value::Table` to make use of this.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::Value;
+    ///
+    /// # fn main() {
+    /// let mut player = Value::table(table!{ "hp" => 10 });
+    /// let saved = player.snapshot().unwrap();
+    /// player.as_table_mut().unwrap().insert("hp".into(), Value::from(5));
+    /// assert_eq!(saved["hp"], Value::from(10));
+    /// player.restore(saved);
+    /// assert_eq!(player.as_table().unwrap()["hp"], Value::from(10));
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> Option<TableSnapshot> {
+        match *self {
+            Value::Table(ref table) => Some(TableSnapshot { table: table.clone() }),
+            _ => None,
+        }
+    }
+
+    /// Replaces the value with a saved table state, in O(1).
+    pub fn restore(&mut self, snapshot: TableSnapshot) {
+        *self = Value::Table(snapshot.table);
+    }
+}
+
+impl Table {
+    /// Saves the current state of the table.
+    ///
+    /// A `Table` owns its entries, so they have to be copied into the
+    /// snapshot. Use `Value::snapshot` on a `Value::Table` instead to
+    /// save the state in O(1).
+    ///
+    /// Only the top level entries are copied, once. Long strings, bytes,
+    /// arrays and nested tables are shared with the table through `Arc`,
+    /// and are copied only when the table changes them afterwards. Taking
+    /// a snapshot therefore costs time proportional to the number of
+    /// entries, not to the size of the nested data. The snapshot itself is
+    /// shared, so cloning it is O(1), and `restore` moves the saved state
+    /// back without copying it unless the snapshot has been cloned.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// # fn main() {
+    /// let mut player = table!{ "hp" => 10, "pos" => table!{ "x" => 1.0 } };
+    /// let saved = player.snapshot();
+    /// player.set_path("pos.x", 2.0.into()).unwrap();
+    /// player.restore(saved);
+    /// assert_eq!(player.get_path("pos.x"), Some(&1.0.into()));
+    /// # }
+    /// ```
+    pub fn snapshot(&self) -> TableSnapshot {
+        TableSnapshot { table: Arc::new(self.clone()) }
+    }
+
+    /// Replaces the table with a saved state.
+    ///
+    /// The saved top level entries are copied only if other clones of the
+    /// snapshot still share them.
+    pub fn restore(&mut self, snapshot: TableSnapshot) {
+        *self = Arc::try_unwrap(snapshot.table).unwrap_or_else(|table| (*table).clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use { Table, Value };
+
+    #[test]
+    fn test_snapshot() {
+        let mut a = table!{
+            "hp" => 10,
+            "pos" => table!{ "x" => 1.0 },
+            "tags" => vec![Value::str("a")],
+        };
+        let before = a.clone();
+        let saved = a.snapshot();
+        match (&a["pos"], &saved["pos"]) {
+            (&Value::Table(ref x), &Value::Table(ref y)) => assert!(Arc::ptr_eq(x, y)),
+            _ => unreachable!(),
+        }
+
+        a.set_path("pos.x", Value::f64(2.0)).unwrap();
+        a.entry_array("tags").push(Value::str("b"));
+        a.remove(&Value::str("hp"));
+        a.insert(Value::str("new"), Value::Bool(true));
+        assert_eq!(*saved, before);
+
+        let copy = saved.clone();
+        assert!(Arc::ptr_eq(&copy.table, &saved.table));
+        a.restore(saved);
+        assert_eq!(a, before);
+        a.clear();
+        a.restore(copy);
+        assert_eq!(a, before);
+        let mut b = Table::new();
+        let empty = b.snapshot();
+        b.push(Value::Null);
+        b.restore(empty);
+        assert!(b.is_empty());
+    }
+
+    #[test]
+    fn test_value_snapshot() {
+        let mut a = Value::table(table!{ "hp" => 10, "pos" => table!{ "x" => 1.0 } });
+        let before = a.clone();
+        let saved = a.snapshot().unwrap();
+        match a {
+            Value::Table(ref table) => assert!(Arc::ptr_eq(table, &saved.table)),
+            _ => unreachable!(),
+        }
+
+        a.as_table_mut().unwrap().insert(Value::str("hp"), Value::Usize(5));
+        assert_eq!(Value::Table(saved.table.clone()), before);
+        a.restore(saved.clone());
+        assert_eq!(a, before);
+        match a {
+            Value::Table(ref table) => assert!(Arc::ptr_eq(table, &saved.table)),
+            _ => unreachable!(),
+        }
+        assert!(Value::Usize(1).snapshot().is_none());
+    }
+}
@@ -0,0 +1,222 @@
+//! Strings stored inline when they are short.
+
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{ Hash, Hasher };
+use std::mem::size_of;
+use std::ops::Deref;
+use std::str;
+use std::sync::Arc;
+
+/// The longest string in bytes that is stored without heap allocation.
+const INLINE_CAP: usize = 14;
+
+/// An immutable string, stored inline when it is short.
+///
+/// Strings of up to 14 bytes are kept inside the value, so creating them
+/// does not allocate. Longer strings are shared through `Arc`, so cloning
+/// never copies them. A `Str` dereferences to `str`, and compares, orders
+/// and hashes like its contents.
+#[derive(Clone)]
+pub struct Str(Repr);
+
+#[derive(Clone)]
+enum Repr {
+    Inline { len: u8, buf: [u8; INLINE_CAP] },
+    Shared(Arc<String>),
+}
+
+impl Str {
+    /// Creates a string, copying the text.
+    pub fn new(text: &str) -> Str {
+        if text.len() <= INLINE_CAP {
+            let mut buf = [0; INLINE_CAP];
+            buf[..text.len()].copy_from_slice(text.as_bytes());
+            Str(Repr::Inline { len: text.len() as u8, buf })
+        } else {
+            Str(Repr::Shared(Arc::new(text.to_owned())))
+        }
+    }
+
+    /// Borrows the text.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        match self.0 {
+            Repr::Inline { len, ref buf } => {
+                // Inline bytes are only ever copied from a `&str`.
+                unsafe { str::from_utf8_unchecked(&buf[..len as usize]) }
+            }
+            Repr::Shared(ref text) => text,
+        }
+    }
+
+    /// Returns `true` if the text is stored inline.
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+
+    /// Returns `true` if both strings share the same heap allocation.
+    ///
+    /// Inline strings never share, so this is `false` for them.
+    pub fn ptr_eq(a: &Str, b: &Str) -> bool {
+        match (&a.0, &b.0) {
+            (&Repr::Shared(ref a), &Repr::Shared(ref b)) => Arc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+
+    /// Estimates the heap memory owned by the string, in bytes.
+    ///
+    /// Inline strings own none. A shared string counts in full, even if
+    /// other strings share it, including the reference counts of the `Arc`.
+    pub fn memory_usage(&self) -> usize {
+        match self.0 {
+            Repr::Inline { .. } => 0,
+            Repr::Shared(ref text) => {
+                2 * size_of::<usize>() + size_of::<String>() + text.capacity()
+            }
+        }
+    }
+
+    /// Converts into an owned `String`.
+    ///
+    /// A shared string is moved out without copying if this is the only
+    /// reference to it.
+    pub fn into_string(self) -> String {
+        match self.0 {
+            Repr::Inline { .. } => self.as_str().to_owned(),
+            Repr::Shared(text) => {
+                Arc::try_unwrap(text).unwrap_or_else(|text| (*text).clone())
+            }
+        }
+    }
+}
+
+impl Deref for Str {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<str> for Str {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl Borrow<str> for Str {
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<'a> From<&'a str> for Str {
+    fn from(text: &'a str) -> Str {
+        Str::new(text)
+    }
+}
+
+impl From<String> for Str {
+    fn from(text: String) -> Str {
+        if text.len() <= INLINE_CAP {
+            Str::new(&text)
+        } else {
+            Str(Repr::Shared(Arc::new(text)))
+        }
+    }
+}
+
+impl From<Arc<String>> for Str {
+    /// Wraps a shared string without copying, even if it is short.
+    fn from(text: Arc<String>) -> Str {
+        Str(Repr::Shared(text))
+    }
+}
+
+impl PartialEq for Str {
+    fn eq(&self, other: &Str) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl Eq for Str {}
+
+impl PartialEq<str> for Str {
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<'a> PartialEq<&'a str> for Str {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+impl PartialOrd for Str {
+    fn partial_cmp(&self, other: &Str) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Str {
+    fn cmp(&self, other: &Str) -> Ordering {
+        self.as_str().cmp(other.as_str())
+    }
+}
+
+impl Hash for Str {
+    fn hash<S>(&self, state: &mut S) where S: Hasher {
+        self.as_str().hash(state)
+    }
+}
+
+impl fmt::Debug for Str {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for Str {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::hash_map::DefaultHasher;
+
+    fn hash_of<T: Hash + ?Sized>(val: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        val.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn test_str() {
+        let short = Str::new("fourteen bytes");
+        let long = Str::new("fifteen bytes!!");
+        assert!(short.is_inline());
+        assert!(!long.is_inline());
+        assert!(Str::new("").is_inline());
+        assert_eq!(short, "fourteen bytes");
+        assert_eq!(&*long, "fifteen bytes!!");
+        assert_eq!(Str::from(Arc::new("fourteen bytes".to_owned())), short);
+        assert_eq!(hash_of(&short), hash_of("fourteen bytes"));
+        let shared = Str::from(Arc::new("a".to_owned()));
+        assert_eq!(hash_of(&shared), hash_of(&Str::new("a")));
+        assert!(Str::new("a") < Str::new("b"));
+        assert_eq!(format!("{:?} {}", Str::new("a"), Str::new("b")), "\"a\" b");
+
+        assert!(Str::ptr_eq(&long, &long.clone()));
+        assert!(!Str::ptr_eq(&short, &short.clone()));
+        assert_eq!(long.into_string(), "fifteen bytes!!");
+        assert_eq!(Str::from("é".to_string()).into_string(), "é");
+    }
+}
@@ -0,0 +1,749 @@
+//! Reading and writing tables as TOML, enabled by the `toml` feature.
+//!
+//! The parser and writer are implemented here, so the feature has no
+//! dependency.
+
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fmt::{ self, Write };
+use std::sync::Arc;
+
+use json::{ ParseError, MAX_DEPTH };
+use { Table, Value };
+
+impl Table {
+    /// Parses a TOML document into a table.
+    ///
+    /// TOML tables become tables keyed by strings, and arrays, including
+    /// arrays of tables, become `Array` values. Non-negative integers
+    /// become `Usize` and negative ones `I64`. Dates and times have no
+    /// value kind, so they are kept as strings in their TOML form, like
+    /// `1979-05-27T07:32:00Z`.
+    ///
+    /// The parser is less strict than the specification in one way: a
+    /// table header may be repeated, and then adds to the same table.
+    ///
+    /// Arrays and inline tables may be nested at most 128 levels deep,
+    /// like in `Value::from_json_str`.
+    pub fn from_toml_str(text: &str) -> Result<Table, ParseError> {
+        let mut parser = Parser { text, pos: 0, depth: 0 };
+        parser.document()
+    }
+
+    /// Writes the table as a TOML document.
+    ///
+    /// Entries are sorted by key, with plain values before nested tables.
+    /// Arrays that hold only tables are written as arrays of tables, and
+    /// tables with keys `0..n` are written as arrays like in JSON output.
+    /// TOML has no null, so `Null` values are left out, and arrays holding
+    /// `Null` are written as tables keyed by index, so that the other
+    /// values keep their index. Non-string keys are written as their
+    /// `Display` form. Chars are written as strings and bytes as base64
+    /// strings, and none of them read back as their original kind.
+    pub fn to_toml_string(&self) -> String {
+        let mut out = String::new();
+        write_table(&mut out, self, &mut vec![]).unwrap();
+        out
+    }
+}
+
+/// Returns the values of a table or array that is written as an array.
+///
+/// Arrays holding `Null` are not, since leaving it out would shift the
+/// values after it.
+fn array_items(val: &Value) -> Option<Vec<&Value>> {
+    let items: Vec<&Value> = match *val {
+        Value::Array(ref items) => items.iter().collect(),
+        Value::Table(ref table) if !table.is_empty() && table.is_array() => {
+            (0..table.len()).map(|i| &table[i]).collect()
+        }
+        _ => return None,
+    };
+    if items.iter().any(|item| item.is_null()) { None } else { Some(items) }
+}
+
+/// Returns a table or array that is written as a table, with arrays keyed
+/// by index.
+fn table_items<'a>(val: &'a Value) -> Option<Cow<'a, Table>> {
+    match *val {
+        Value::Table(ref table) => Some(Cow::Borrowed(&**table)),
+        Value::Array(ref items) => {
+            Some(Cow::Owned(items.iter().cloned().enumerate().collect()))
+        }
+        _ => None,
+    }
+}
+
+fn is_table_array(val: &Value) -> bool {
+    array_items(val).is_some_and(|items| {
+        !items.is_empty() && items.iter().all(|item| item.is_table())
+    })
+}
+
+fn key_text(key: &Value) -> String {
+    match key.as_str() {
+        Some(text) => text.to_owned(),
+        None => key.to_string(),
+    }
+}
+
+fn write_table<W: Write>(w: &mut W, table: &Table, path: &mut Vec<String>)
+    -> fmt::Result
+{
+    let entries: Vec<(&Value, &Value)> = table.sorted_entries().into_iter()
+        .filter(|&(_, val)| !val.is_null())
+        .collect();
+    let nested = |val: &Value| {
+        (val.is_table() || val.is_array()) && array_items(val).is_none()
+            || is_table_array(val)
+    };
+    for &(key, val) in entries.iter().filter(|&&(_, val)| !nested(val)) {
+        write_key(w, &key_text(key))?;
+        w.write_str(" = ")?;
+        write_inline(w, val)?;
+        w.write_char('\n')?;
+    }
+    for &(key, val) in entries.iter().filter(|&&(_, val)| nested(val)) {
+        path.push(key_text(key));
+        match array_items(val) {
+            Some(items) => {
+                for item in items {
+                    write_header(w, path, true)?;
+                    write_table(w, item.as_table().unwrap(), path)?;
+                }
+            }
+            None => {
+                write_header(w, path, false)?;
+                write_table(w, &table_items(val).unwrap(), path)?;
+            }
+        }
+        path.pop();
+    }
+    Ok(())
+}
+
+fn write_header<W: Write>(w: &mut W, path: &[String], array: bool) -> fmt::Result {
+    w.write_str(if array { "\n[[" } else { "\n[" })?;
+    for (i, key) in path.iter().enumerate() {
+        if i > 0 { w.write_char('.')?; }
+        write_key(w, key)?;
+    }
+    w.write_str(if array { "]]\n" } else { "]\n" })
+}
+
+/// Returns `true` for characters allowed in bare keys.
+fn is_bare(ch: char) -> bool {
+    ch.is_ascii_alphanumeric() || ch == '_' || ch == '-'
+}
+
+fn write_key<W: Write>(w: &mut W, key: &str) -> fmt::Result {
+    if !key.is_empty() && key.chars().all(is_bare) {
+        w.write_str(key)
+    } else {
+        write_str(w, key)
+    }
+}
+
+fn write_inline<W: Write>(w: &mut W, val: &Value) -> fmt::Result {
+    if let Some(items) = array_items(val) {
+        w.write_char('[')?;
+        let items = items.into_iter().filter(|item| !item.is_null());
+        for (i, item) in items.enumerate() {
+            if i > 0 { w.write_str(", ")?; }
+            write_inline(w, item)?;
+        }
+        return w.write_char(']');
+    }
+    match *val {
+        Value::Null => Ok(()),
+        Value::Bool(x) => write!(w, "{}", x),
+        Value::Usize(x) => write!(w, "{}", x),
+        Value::U64(x) => write!(w, "{}", x),
+        Value::I64(x) => write!(w, "{}", x),
+        Value::F64(x) => {
+            if x.0.is_nan() {
+                w.write_str("nan")
+            } else if x.0.is_infinite() {
+                w.write_str(if x.0 > 0.0 { "inf" } else { "-inf" })
+            } else {
+                write!(w, "{:?}", x.0)
+            }
+        }
+        Value::Char(ch) => write_str(w, ch.encode_utf8(&mut [0; 4])),
+        Value::String(ref text) => write_str(w, text),
+        Value::Bytes(_) => write_str(w, &val.to_string()),
+        Value::Array(_) | Value::Table(_) => {
+            let table = table_items(val).unwrap();
+            let entries: Vec<(&Value, &Value)> = table.sorted_entries().into_iter()
+                .filter(|&(_, val)| !val.is_null())
+                .collect();
+            if entries.is_empty() {
+                return w.write_str("{}");
+            }
+            w.write_str("{ ")?;
+            for (i, (key, val)) in entries.into_iter().enumerate() {
+                if i > 0 { w.write_str(", ")?; }
+                write_key(w, &key_text(key))?;
+                w.write_str(" = ")?;
+                write_inline(w, val)?;
+            }
+            w.write_str(" }")
+        }
+    }
+}
+
+fn write_str<W: Write>(w: &mut W, text: &str) -> fmt::Result {
+    w.write_char('"')?;
+    for ch in text.chars() {
+        match ch {
+            '"' => w.write_str("\\\"")?,
+            '\\' => w.write_str("\\\\")?,
+            '\n' => w.write_str("\\n")?,
+            '\r' => w.write_str("\\r")?,
+            '\t' => w.write_str("\\t")?,
+            ch if ch.is_control() => write!(w, "\\u{:04x}", ch as u32)?,
+            ch => w.write_char(ch)?,
+        }
+    }
+    w.write_char('"')
+}
+
+/// Borrows the table under a key, creating it if missing.
+///
+/// For an array of tables, the last table of the array is borrowed.
+fn descend<'t>(table: &'t mut Table, key: &str) -> Option<&'t mut Table> {
+    let val = table.0.entry(Value::str(key))
+        .or_insert_with(|| Value::table(Table::new()));
+    match *val {
+        Value::Table(ref mut table) => Some(Arc::make_mut(table)),
+        Value::Array(ref mut items) => match Arc::make_mut(items).last_mut() {
+            Some(&mut Value::Table(ref mut table)) => Some(Arc::make_mut(table)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    /// The number of arrays and inline tables being parsed.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> ParseError {
+        ParseError { offset: self.pos, message: message.into() }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.peek()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn eat(&mut self, literal: &str) -> bool {
+        if self.rest().starts_with(literal) {
+            self.pos += literal.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, literal: &str) -> Result<(), ParseError> {
+        if self.eat(literal) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", literal)))
+        }
+    }
+
+    /// Skips spaces and tabs.
+    fn skip_space(&mut self) {
+        let trimmed = self.rest().trim_start_matches([' ', '\t']);
+        self.pos = self.text.len() - trimmed.len();
+    }
+
+    /// Skips whitespace, line breaks and comments.
+    fn skip_lines(&mut self) {
+        loop {
+            let trimmed = self.rest().trim_start_matches([' ', '\t', '\r', '\n']);
+            self.pos = self.text.len() - trimmed.len();
+            if self.peek() != Some('#') {
+                return;
+            }
+            self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+        }
+    }
+
+    /// Expects the end of a line, allowing a comment.
+    fn end_of_line(&mut self) -> Result<(), ParseError> {
+        self.skip_space();
+        if self.peek() == Some('#') {
+            self.pos += self.rest().find('\n').unwrap_or(self.rest().len());
+        }
+        if self.peek().is_none() || self.eat("\n") || self.eat("\r\n") {
+            Ok(())
+        } else {
+            Err(self.error("expected end of line"))
+        }
+    }
+
+    fn document(&mut self) -> Result<Table, ParseError> {
+        let mut root = Table::new();
+        let mut current: Vec<String> = vec![];
+        loop {
+            self.skip_lines();
+            if self.peek().is_none() {
+                return Ok(root);
+            }
+            let start = self.pos;
+            if self.eat("[[") {
+                let path = self.key()?;
+                self.expect("]]")?;
+                let (last, parents) = path.split_last().unwrap();
+                let table = self.table_at(&mut root, parents, start)?;
+                let val = table.0.entry(Value::str(last))
+                    .or_insert_with(|| Value::array(vec![]));
+                match *val {
+                    Value::Array(ref mut items) => {
+                        Arc::make_mut(items).push(Value::table(Table::new()));
+                    }
+                    _ => {
+                        self.pos = start;
+                        return Err(self.error("key is not an array of tables"));
+                    }
+                }
+                current = path;
+            } else if self.eat("[") {
+                let path = self.key()?;
+                self.expect("]")?;
+                self.table_at(&mut root, &path, start)?;
+                current = path;
+            } else {
+                let path = self.key()?;
+                self.expect("=")?;
+                let val = self.value()?;
+                let (last, parents) = path.split_last().unwrap();
+                let table = self.table_at(&mut root, &current, start)?;
+                let table = self.table_at(table, parents, start)?;
+                if table.contains_key(&Value::str(last)) {
+                    self.pos = start;
+                    return Err(self.error("duplicate key"));
+                }
+                table.insert(Value::str(last), val);
+            }
+            self.end_of_line()?;
+        }
+    }
+
+    fn table_at<'t>(
+        &mut self,
+        mut table: &'t mut Table,
+        path: &[String],
+        start: usize
+    ) -> Result<&'t mut Table, ParseError> {
+        for key in path {
+            table = match descend(table, key) {
+                Some(table) => table,
+                None => {
+                    self.pos = start;
+                    return Err(self.error("key is not a table"));
+                }
+            };
+        }
+        Ok(table)
+    }
+
+    /// Parses a dotted key into its segments.
+    fn key(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut path = vec![];
+        loop {
+            self.skip_space();
+            let segment = match self.peek() {
+                Some('"') => self.basic_string()?,
+                Some('\'') => self.literal_string()?,
+                _ => {
+                    let len = self.rest()
+                        .find(|ch: char| !is_bare(ch))
+                        .unwrap_or(self.rest().len());
+                    if len == 0 {
+                        return Err(self.error("expected key"));
+                    }
+                    self.pos += len;
+                    self.text[self.pos - len..self.pos].to_owned()
+                }
+            };
+            path.push(segment);
+            self.skip_space();
+            if !self.eat(".") {
+                return Ok(path);
+            }
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, ParseError> {
+        self.skip_space();
+        match self.peek() {
+            None => Err(self.error("unexpected end of input")),
+            Some('"') => self.basic_string().map(Value::from),
+            Some('\'') => self.literal_string().map(Value::from),
+            Some('[') => self.nested(Parser::array),
+            Some('{') => self.nested(Parser::inline_table),
+            Some('t') if self.eat("true") => Ok(Value::Bool(true)),
+            Some('f') if self.eat("false") => Ok(Value::Bool(false)),
+            Some(_) => self.number_or_date(),
+        }
+    }
+
+    /// Parses an array or inline table one level deeper, see `MAX_DEPTH`.
+    fn nested<F>(&mut self, parse: F) -> Result<Value, ParseError>
+        where F: FnOnce(&mut Parser<'a>) -> Result<Value, ParseError>
+    {
+        if self.depth == MAX_DEPTH {
+            return Err(self.error("nesting too deep"));
+        }
+        self.depth += 1;
+        let val = parse(self);
+        self.depth -= 1;
+        val
+    }
+
+    fn array(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1;
+        let mut items = vec![];
+        loop {
+            self.skip_lines();
+            if self.eat("]") {
+                return Ok(Value::array(items));
+            }
+            items.push(self.value()?);
+            self.skip_lines();
+            if !self.eat(",") {
+                self.skip_lines();
+                self.expect("]")?;
+                return Ok(Value::array(items));
+            }
+        }
+    }
+
+    fn inline_table(&mut self) -> Result<Value, ParseError> {
+        self.pos += 1;
+        let mut table = Table::new();
+        self.skip_space();
+        if self.eat("}") {
+            return Ok(Value::table(table));
+        }
+        loop {
+            let start = self.pos;
+            let path = self.key()?;
+            self.expect("=")?;
+            let val = self.value()?;
+            let (last, parents) = path.split_last().unwrap();
+            let parent = self.table_at(&mut table, parents, start)?;
+            if parent.contains_key(&Value::str(last)) {
+                self.pos = start;
+                return Err(self.error("duplicate key"));
+            }
+            parent.insert(Value::str(last), val);
+            self.skip_space();
+            if !self.eat(",") {
+                self.expect("}")?;
+                return Ok(Value::table(table));
+            }
+        }
+    }
+
+    fn basic_string(&mut self) -> Result<String, ParseError> {
+        let multiline = self.eat("\"\"\"");
+        if !multiline {
+            self.pos += 1;
+        } else if !self.eat("\n") {
+            self.eat("\r\n");
+        }
+        let mut out = String::new();
+        loop {
+            if multiline && self.rest().starts_with("\"\"\"") {
+                // Up to two quotes may directly precede the closing ones.
+                let rest = self.rest();
+                let quotes = rest.len() - rest.trim_start_matches('"').len();
+                for _ in 0..quotes.min(5) - 3 {
+                    out.push('"');
+                }
+                self.pos += quotes.min(5);
+                return Ok(out);
+            }
+            match self.next_char() {
+                None => return Err(self.error("unterminated string")),
+                Some('"') if !multiline => return Ok(out),
+                Some('\n') if !multiline => return Err(self.error("unterminated string")),
+                Some('\\') => {
+                    let line_end = self.rest().trim_start_matches([' ', '\t'])
+                        .starts_with(['\n', '\r']);
+                    if multiline && line_end {
+                        // A line ending backslash trims the following whitespace.
+                        let trimmed = self.rest().trim_start();
+                        self.pos = self.text.len() - trimmed.len();
+                        continue;
+                    }
+                    out.push(self.escape()?);
+                }
+                Some(ch) => out.push(ch),
+            }
+        }
+    }
+
+    fn escape(&mut self) -> Result<char, ParseError> {
+        let start = self.pos;
+        let ch = match self.next_char() {
+            Some('b') => '\u{8}',
+            Some('t') => '\t',
+            Some('n') => '\n',
+            Some('f') => '\u{c}',
+            Some('r') => '\r',
+            Some('e') => '\u{1b}',
+            Some('"') => '"',
+            Some('\\') => '\\',
+            Some(kind @ 'u') | Some(kind @ 'U') => {
+                let len = if kind == 'u' { 4 } else { 8 };
+                let code = self.rest().get(..len)
+                    .and_then(|digits| u32::from_str_radix(digits, 16).ok())
+                    .and_then(::std::char::from_u32);
+                match code {
+                    Some(ch) => {
+                        self.pos += len;
+                        ch
+                    }
+                    None => return Err(self.error("invalid unicode escape")),
+                }
+            }
+            _ => {
+                self.pos = start;
+                return Err(self.error("invalid escape"));
+            }
+        };
+        Ok(ch)
+    }
+
+    fn literal_string(&mut self) -> Result<String, ParseError> {
+        if self.eat("'''") {
+            if !self.eat("\n") {
+                self.eat("\r\n");
+            }
+            return match self.rest().find("'''") {
+                Some(end) => {
+                    // Up to two quotes may directly precede the closing ones.
+                    let quotes = self.rest()[end..].len() -
+                        self.rest()[end..].trim_start_matches('\'').len();
+                    let end = end + quotes.min(5) - 3;
+                    let text = self.rest()[..end].to_owned();
+                    self.pos += end + 3;
+                    Ok(text)
+                }
+                None => Err(self.error("unterminated string")),
+            };
+        }
+        self.pos += 1;
+        match self.rest().find(['\'', '\n']) {
+            Some(end) if self.rest()[end..].starts_with('\'') => {
+                let text = self.rest()[..end].to_owned();
+                self.pos += end + 1;
+                Ok(text)
+            }
+            _ => Err(self.error("unterminated string")),
+        }
+    }
+
+    fn number_or_date(&mut self) -> Result<Value, ParseError> {
+        let start = self.pos;
+        let token_len = |text: &str| {
+            text.find(|ch: char| {
+                !(ch.is_ascii_alphanumeric() || ['_', '+', '-', '.', ':'].contains(&ch))
+            }).unwrap_or(text.len())
+        };
+        self.pos += token_len(self.rest());
+        let mut token = &self.text[start..self.pos];
+        if token.is_empty() {
+            return Err(self.error("unexpected character"));
+        }
+
+        let bytes = token.as_bytes();
+        let is_date = bytes.len() >= 10 && bytes[4] == b'-' && bytes[7] == b'-';
+        let is_time = bytes.len() >= 8 && bytes[2] == b':';
+        if is_date || is_time {
+            // A space may separate the date from the time.
+            let rest = self.rest();
+            if is_date && token.len() == 10 && rest.starts_with(' ') &&
+                rest.as_bytes().get(3) == Some(&b':')
+            {
+                self.pos += 1 + token_len(&rest[1..]);
+                token = &self.text[start..self.pos];
+            }
+            return Ok(Value::str(token));
+        }
+
+        let text: String = token.chars().filter(|&ch| ch != '_').collect();
+        let (sign, digits) = match text.strip_prefix(['+', '-']) {
+            Some(digits) => (&text[..1], digits),
+            None => ("", &text[..]),
+        };
+        let radix = match digits.get(..2) {
+            Some("0x") => 16,
+            Some("0o") => 8,
+            Some("0b") => 2,
+            _ => 10,
+        };
+        let leading_zero = digits.len() > 1 && digits.starts_with('0') &&
+            digits.as_bytes()[1].is_ascii_digit();
+        let val = if radix != 10 {
+            // `from_str_radix` would accept a sign after the prefix.
+            if sign.is_empty() && digits[2..].chars().all(|ch| ch.is_digit(radix)) {
+                u64::from_str_radix(&digits[2..], radix).ok().map(|x| {
+                    usize::try_from(x).map_or(Value::U64(x), Value::Usize)
+                })
+            } else {
+                None
+            }
+        } else if digits == "inf" {
+            Some(Value::f64(if sign == "-" { f64::NEG_INFINITY } else { f64::INFINITY }))
+        } else if digits == "nan" {
+            Some(Value::f64(f64::NAN))
+        } else if !digits.starts_with(|ch: char| ch.is_ascii_digit()) || leading_zero {
+            None
+        } else if text.contains(['.', 'e', 'E']) {
+            text.parse().ok().map(Value::f64)
+        } else {
+            let digits = text.trim_start_matches('+');
+            digits.parse().map(Value::Usize)
+                .or_else(|_| digits.parse().map(Value::U64))
+                .or_else(|_| digits.parse().map(Value::I64))
+                .ok()
+        };
+        val.ok_or_else(|| {
+            self.pos = start;
+            self.error("invalid value")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use { Table, Value };
+
+    const MANIFEST: &str = r#"
+# A manifest.
+[package]
+name = "table"
+version = "0.1.1"
+authors = [
+    "bvssvni <bvssvni@gmail.com>", # The author.
+]
+edition = '2015'
+description = """
+A table object type \
+    for dynamical data"""
+
+[dependencies]
+serde = { version = "1.0", features = ["derive"], optional = true }
+rand.version = "0.8"
+
+[profile.release]
+opt-level = 3
+lto = true
+debug = 0x1_0
+published = 1979-05-27T07:32:00Z
+
+[[bin]]
+name = "a"
+path = "src/a.rs"
+
+[[bin]]
+name = "b"
+weight = -1.5e2
+"#;
+
+    #[test]
+    fn test_from_toml_str() {
+        let table = Table::from_toml_str(MANIFEST).unwrap();
+        assert_eq!(table, table!{
+            "package" => table!{
+                "name" => "table",
+                "version" => "0.1.1",
+                "authors" => vec![Value::str("bvssvni <bvssvni@gmail.com>")],
+                "edition" => "2015",
+                "description" => "A table object type for dynamical data",
+            },
+            "dependencies" => table!{
+                "serde" => table!{
+                    "version" => "1.0",
+                    "features" => vec![Value::str("derive")],
+                    "optional" => true,
+                },
+                "rand" => table!{ "version" => "0.8" },
+            },
+            "profile" => table!{
+                "release" => table!{
+                    "opt-level" => 3usize,
+                    "lto" => true,
+                    "debug" => 16usize,
+                    "published" => "1979-05-27T07:32:00Z",
+                },
+            },
+            "bin" => vec![
+                Value::table(table!{ "name" => "a", "path" => "src/a.rs" }),
+                Value::table(table!{ "name" => "b", "weight" => -150.0 }),
+            ],
+        });
+
+        let text = "a = [-1, 'x', inf, [], {}]\nb = 1979-05-27 07:32:00";
+        let table = Table::from_toml_str(text).unwrap();
+        assert_eq!(table["a"], Value::array(vec![Value::I64(-1), Value::str("x"),
+            Value::f64(f64::INFINITY), Value::array(vec![]),
+            Value::table(Table::new())]));
+        assert_eq!(table["b"], Value::str("1979-05-27 07:32:00"));
+
+        for text in &["a", "a = ", "a = 1\na = 2", "a = 1\n[a]", "a = 1 b = 2",
+                      "a = \"x", "a = 0x", "[a\nb = 1", "a = { b = 1", "a = -0b1",
+                      "a = 0x+10", "a = 0o-7", "a = 007", "a = -01", "a = 00.5"] {
+            assert!(Table::from_toml_str(text).is_err(), "{}", text);
+        }
+        assert_eq!(Table::from_toml_str("a = 1\nb = ?").unwrap_err().offset, 10);
+    }
+
+    #[test]
+    fn test_nesting_depth() {
+        for open in &["[", "{ a = "] {
+            let text = format!("a = {}", open.repeat(100_000));
+            let err = Table::from_toml_str(&text).unwrap_err();
+            assert_eq!(err.message, "nesting too deep", "{}", open);
+        }
+        let deepest = format!("a = {}{}", "[".repeat(128), "]".repeat(128));
+        assert!(Table::from_toml_str(&deepest).is_ok());
+    }
+
+    #[test]
+    fn test_to_toml_string() {
+        let table = Table::from_toml_str(MANIFEST).unwrap();
+        assert_eq!(Table::from_toml_str(&table.to_toml_string()).unwrap(), table);
+
+        let table = table!{
+            "b" => table!{ 0usize => 1usize, 1usize => Value::Null, 2usize => 'c' },
+            "a b" => "x\ny",
+            "t" => table!{ "inner" => table!{ 1usize => Value::f64(2.0) } },
+            "n" => Value::Null,
+            "v" => vec![Value::array(vec![Value::Null, Value::Usize(1)])],
+        };
+        assert_eq!(table.to_toml_string(), "\"a b\" = \"x\\ny\"\nv = [{ 1 = 1 }]\n\
+            \n[b]\n0 = 1\n2 = \"c\"\n\n[t]\n\n[t.inner]\n1 = 2.0\n");
+        assert_eq!(Table::new().to_toml_string(), "");
+    }
+}
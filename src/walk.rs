@@ -0,0 +1,606 @@
+//! Traversal of nested tables.
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use path::parse_index;
+use { Table, Value };
+
+/// One step of the path from a table to a nested value.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum PathSegment {
+    /// The key of a table entry.
+    Key(Value),
+    /// The index of an array element.
+    Index(usize),
+}
+
+/// An error returned when a traversal exceeds its maximum depth.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct DepthError {
+    /// The maximum depth that was exceeded.
+    pub max_depth: usize,
+}
+
+impl fmt::Display for DepthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "maximum depth of {} exceeded", self.max_depth)
+    }
+}
+
+impl Error for DepthError {}
+
+/// An error returned when flat keys describe conflicting structure.
+///
+/// This happens when one key is a prefix of another, like `a.b` and
+/// `a.b.c`, since `a.b` can not be both a value and a table.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyConflict {
+    /// The key that conflicts with an earlier one.
+    pub key: Value,
+}
+
+impl fmt::Display for KeyConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "conflicting key {}", self.key)
+    }
+}
+
+impl Error for KeyConflict {}
+
+impl Table {
+    /// Calls a closure for every leaf value with its path from this table.
+    ///
+    /// Nested tables and arrays are walked into, and all other values are
+    /// leaves. The entries of this table have paths of length 1. If a value
+    /// with a path longer than `max_depth` is found, the walk stops with an
+    /// error. Entries are visited in no particular order.
+    pub fn walk<F>(&self, max_depth: usize, mut f: F) -> Result<(), DepthError>
+        where F: FnMut(&[PathSegment], &Value)
+    {
+        let mut path = vec![];
+        walk_table(self, &mut path, max_depth, &mut f)
+    }
+
+    /// Counts the leaf values in this table and all nested containers.
+    ///
+    /// Leaves are all values except tables and arrays, like in `walk`.
+    /// An explicit stack is used, so deep nesting can not overflow.
+    pub fn deep_len(&self) -> usize {
+        let mut count = 0;
+        let mut stack: Vec<&Value> = self.values().collect();
+        while let Some(val) = stack.pop() {
+            match *val {
+                Value::Table(ref table) => stack.extend(table.values()),
+                Value::Array(ref items) => stack.extend(items.iter()),
+                _ => count += 1,
+            }
+        }
+        count
+    }
+
+    /// Returns the maximum nesting depth.
+    ///
+    /// This is the length of the longest path to any value, so an empty
+    /// table has depth 0 and a table without nested containers depth 1.
+    /// An explicit stack is used, so deep nesting can not overflow.
+    pub fn depth(&self) -> usize {
+        let mut max = 0;
+        let mut stack: Vec<(&Value, usize)> =
+            self.values().map(|val| (val, 1)).collect();
+        while let Some((val, depth)) = stack.pop() {
+            max = max.max(depth);
+            match *val {
+                Value::Table(ref table) => {
+                    stack.extend(table.values().map(|val| (val, depth + 1)));
+                }
+                Value::Array(ref items) => {
+                    stack.extend(items.iter().map(|val| (val, depth + 1)));
+                }
+                _ => {}
+            }
+        }
+        max
+    }
+
+    /// Returns a copy with every leaf value mapped by a closure.
+    ///
+    /// Leaves are all values except tables and arrays, like in `walk`, so
+    /// values inside arrays are mapped too. Keys are kept. A nested table
+    /// or array whose leaves all map to themselves, with the same kind
+    /// and an equal value, is shared with this table instead of rebuilt.
+    /// An explicit stack is used, so deep nesting can not overflow.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::Value;
+    ///
+    /// # fn main() {
+    /// let a = table!{ "pos" => table!{ "x" => 1.25, "y" => vec![Value::from(2.75)] } };
+    /// let rounded = a.deep_map_values(|val| match val.as_f64() {
+    ///     Some(x) => Value::from(x.round()),
+    ///     None => val.clone(),
+    /// });
+    /// assert_eq!(rounded.get_path("pos.x"), Some(&Value::from(1.0)));
+    /// assert_eq!(rounded.get_path("pos.y.0"), Some(&Value::from(3.0)));
+    /// # }
+    /// ```
+    pub fn deep_map_values<F>(&self, f: F) -> Table
+        where F: FnMut(&Value) -> Value
+    {
+        deep_map(self, false, Value::clone, f)
+    }
+
+    /// Returns a copy with every key mapped by a closure, in this table
+    /// and all nested tables.
+    ///
+    /// Tables inside arrays are mapped too, like in `walk`. The closure
+    /// sees each key as it is, and keys that are themselves tables are
+    /// not walked into. Values are kept, and nested containers where no
+    /// key changes are shared with this table instead of rebuilt. An
+    /// explicit stack is used, so deep nesting can not overflow.
+    ///
+    /// When several keys of one table map to the same key, the last one
+    /// wins: the entry whose original key is greatest, ordered like
+    /// `Ord for Value`, is kept and the others are dropped.
+    ///
+    /// ```
+    /// #[macro_use]
+    /// extern crate table;
+    ///
+    /// use table::Value;
+    ///
+    /// # fn main() {
+    /// let a = table!{ "Name" => "Sven", "POS" => table!{ "X" => 1.0 } };
+    /// let b = a.deep_map_keys(|key| match key.as_str() {
+    ///     Some(text) => Value::from(text.to_lowercase()),
+    ///     None => key.clone(),
+    /// });
+    /// assert_eq!(b, table!{ "name" => "Sven", "pos" => table!{ "x" => 1.0 } });
+    /// # }
+    /// ```
+    pub fn deep_map_keys<F>(&self, f: F) -> Table
+        where F: FnMut(&Value) -> Value
+    {
+        deep_map(self, true, f, Value::clone)
+    }
+
+    /// Turns nested tables into a single table with dotted string keys.
+    ///
+    /// For example `{ a: { b: 1 } }` becomes `{ "a.b": 1 }`. Non-string
+    /// keys are written in their `Display` form, so integer keys appear
+    /// as decimal numbers. Empty nested tables are kept as values.
+    ///
+    /// Different keys can flatten to the same string, like `"a.b"` next
+    /// to `{ a: { b: .. } }`, or `1` next to `"1"`. Then the last one wins:
+    /// entries are visited in key order, ordered like `Ord for Value`, and
+    /// a later entry replaces an earlier one. Use `unflatten` to check
+    /// that flat keys do not conflict.
+    pub fn flatten(&self) -> Table {
+        self.flatten_with(".")
+    }
+
+    /// Works like `flatten`, but joins keys with a custom separator.
+    pub fn flatten_with(&self, separator: &str) -> Table {
+        let mut out = Table::new();
+        flatten_into(self, None, separator, &mut out);
+        out
+    }
+
+    /// Rebuilds nested tables from dotted string keys, see `flatten`.
+    ///
+    /// Segments that are decimal numbers become `Usize` keys and other
+    /// segments become string keys. Non-string keys are kept as they are.
+    /// Returns an error if a key is a prefix of another key.
+    pub fn unflatten(&self) -> Result<Table, KeyConflict> {
+        self.unflatten_with(".")
+    }
+
+    /// Works like `unflatten`, but splits keys on a custom separator.
+    pub fn unflatten_with(&self, separator: &str) -> Result<Table, KeyConflict> {
+        let mut out = Table::new();
+        for (key, val) in self.sorted_entries() {
+            let conflict = || KeyConflict { key: key.clone() };
+            let text = match *key {
+                Value::String(ref text) => text,
+                _ => {
+                    if out.contains_key(key) {
+                        return Err(conflict());
+                    }
+                    out.insert(key.clone(), val.clone());
+                    continue;
+                }
+            };
+
+            let segments: Vec<&str> = text.split(separator).collect();
+            let (last, parents) = segments.split_last().unwrap();
+            let mut table = &mut out;
+            for segment in parents {
+                let val = table.entry(segment_key(segment))
+                    .or_insert_with(|| Value::table(Table::new()));
+                table = match *val {
+                    Value::Table(ref mut table) => Arc::make_mut(table),
+                    _ => return Err(conflict()),
+                };
+            }
+            let key = segment_key(last);
+            if table.contains_key(&key) {
+                return Err(conflict());
+            }
+            table.insert(key, val.clone());
+        }
+        Ok(out)
+    }
+}
+
+/// Rebuilds a table with mapped keys and leaf values.
+///
+/// With `sorted` set, the entries of each table are mapped in key order,
+/// so that the greatest original key wins when mapped keys collide.
+fn deep_map<K, F>(table: &Table, sorted: bool, mut map_key: K, mut map_leaf: F) -> Table
+    where K: FnMut(&Value) -> Value, F: FnMut(&Value) -> Value
+{
+    let mut stack = vec![Frame::table(None, table, sorted, &mut map_key)];
+    loop {
+        let child = {
+            let top = stack.last().unwrap();
+            top.children.get(top.done.len()).cloned()
+        };
+        match child {
+            Some(child) if child.is_table() || child.is_array() => {
+                stack.push(Frame::new(child, sorted, &mut map_key));
+            }
+            Some(child) => {
+                let mapped = map_leaf(child);
+                let top = stack.last_mut().unwrap();
+                top.changed |= !same(&mapped, child);
+                top.done.push(mapped);
+            }
+            None => {
+                let frame = stack.pop().unwrap();
+                let changed = frame.changed;
+                match stack.last_mut() {
+                    Some(parent) => {
+                        parent.changed |= changed;
+                        parent.done.push(frame.finish());
+                    }
+                    None if changed => return frame.finish_table(),
+                    None => return table.clone(),
+                }
+            }
+        }
+    }
+}
+
+/// Returns `true` if the values are equal and of the same kind.
+fn same(a: &Value, b: &Value) -> bool {
+    a.kind() == b.kind() && a == b
+}
+
+/// A container being rebuilt by `deep_map`.
+struct Frame<'a> {
+    /// The container, or `None` for the table the traversal started in.
+    original: Option<&'a Value>,
+    /// The mapped keys of a table, or `None` for an array.
+    keys: Option<Vec<Value>>,
+    children: Vec<&'a Value>,
+    /// The mapped children so far.
+    done: Vec<Value>,
+    changed: bool,
+}
+
+impl<'a> Frame<'a> {
+    fn new<K>(val: &'a Value, sorted: bool, map_key: &mut K) -> Frame<'a>
+        where K: FnMut(&Value) -> Value
+    {
+        match *val {
+            Value::Table(ref table) => Frame::table(Some(val), table, sorted, map_key),
+            Value::Array(ref items) => Frame {
+                original: Some(val),
+                keys: None,
+                children: items.iter().collect(),
+                done: Vec::with_capacity(items.len()),
+                changed: false,
+            },
+            _ => unreachable!(),
+        }
+    }
+
+    fn table<K>(original: Option<&'a Value>, table: &'a Table, sorted: bool,
+                map_key: &mut K) -> Frame<'a>
+        where K: FnMut(&Value) -> Value
+    {
+        let entries = if sorted {
+            table.sorted_entries()
+        } else {
+            table.iter().collect()
+        };
+        let mut changed = false;
+        let mut keys = Vec::with_capacity(entries.len());
+        let mut children = Vec::with_capacity(entries.len());
+        for (key, val) in entries {
+            let mapped = map_key(key);
+            changed |= !same(&mapped, key);
+            keys.push(mapped);
+            children.push(val);
+        }
+        Frame {
+            original,
+            keys: Some(keys),
+            children,
+            done: Vec::with_capacity(table.len()),
+            changed,
+        }
+    }
+
+    fn finish(self) -> Value {
+        match (self.changed, self.original) {
+            (false, Some(original)) => original.clone(),
+            _ if self.keys.is_some() => Value::table(self.finish_table()),
+            _ => Value::array(self.done),
+        }
+    }
+
+    fn finish_table(self) -> Table {
+        self.keys.unwrap().into_iter().zip(self.done).collect()
+    }
+}
+
+fn flatten_into(table: &Table, prefix: Option<&str>, separator: &str, out: &mut Table) {
+    for (key, val) in table.sorted_entries() {
+        let name = match *key {
+            Value::String(ref text) => text.as_str().to_owned(),
+            _ => key.to_string(),
+        };
+        let name = match prefix {
+            Some(prefix) => format!("{}{}{}", prefix, separator, name),
+            None => name,
+        };
+        match *val {
+            Value::Table(ref table) if !table.is_empty() => {
+                flatten_into(table, Some(&name), separator, out);
+            }
+            _ => {
+                out.insert(Value::from(name), val.clone());
+            }
+        }
+    }
+}
+
+fn segment_key(segment: &str) -> Value {
+    match parse_index(segment) {
+        Some(index) => Value::Usize(index),
+        None => Value::str(segment),
+    }
+}
+
+fn walk_table<F>(
+    table: &Table,
+    path: &mut Vec<PathSegment>,
+    max_depth: usize,
+    f: &mut F
+) -> Result<(), DepthError>
+    where F: FnMut(&[PathSegment], &Value)
+{
+    for (key, val) in table.iter() {
+        path.push(PathSegment::Key(key.clone()));
+        walk_value(val, path, max_depth, f)?;
+        path.pop();
+    }
+    Ok(())
+}
+
+fn walk_value<F>(
+    val: &Value,
+    path: &mut Vec<PathSegment>,
+    max_depth: usize,
+    f: &mut F
+) -> Result<(), DepthError>
+    where F: FnMut(&[PathSegment], &Value)
+{
+    if path.len() > max_depth {
+        return Err(DepthError { max_depth });
+    }
+    match *val {
+        Value::Table(ref table) => walk_table(table, path, max_depth, f),
+        Value::Array(ref items) => {
+            for (i, item) in items.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                walk_value(item, path, max_depth, f)?;
+                path.pop();
+            }
+            Ok(())
+        }
+        _ => {
+            f(path, val);
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_walk() {
+        let a = table!{
+            "name" => "Sven",
+            "pos" => table!{ 0 => 1.0, 1 => 2.0 },
+            "tags" => vec![Value::str("a"), Value::table(table!{ "b" => true })],
+            "empty" => Table::new(),
+        };
+        let mut leaves = vec![];
+        a.walk(8, |path, val| leaves.push((path.to_vec(), val.clone()))).unwrap();
+        leaves.sort();
+
+        let key = |key: &str| PathSegment::Key(Value::str(key));
+        assert_eq!(leaves, vec![
+            (vec![key("name")], Value::str("Sven")),
+            (vec![key("pos"), PathSegment::Key(Value::Usize(0))], Value::f64(1.0)),
+            (vec![key("pos"), PathSegment::Key(Value::Usize(1))], Value::f64(2.0)),
+            (vec![key("tags"), PathSegment::Index(0)], Value::str("a")),
+            (vec![key("tags"), PathSegment::Index(1), key("b")], Value::Bool(true)),
+        ]);
+
+        assert!(a.walk(3, |_, _| {}).is_ok());
+        assert_eq!(a.walk(2, |_, _| {}), Err(DepthError { max_depth: 2 }));
+        assert!(table!{ "a" => 1 }.walk(1, |_, _| {}).is_ok());
+    }
+
+    #[test]
+    fn test_deep_len_and_depth() {
+        let a = table!{
+            "name" => "Sven",
+            "pos" => table!{ 0 => 1.0, 1 => table!{ "z" => 2.0 } },
+            "tags" => vec![Value::str("a"), Value::str("b")],
+            "empty" => Table::new(),
+        };
+        assert_eq!(a.deep_len(), 5);
+        assert_eq!(a.depth(), 3);
+        assert_eq!(Table::new().deep_len(), 0);
+        assert_eq!(Table::new().depth(), 0);
+        assert_eq!(table!{ "a" => Table::new() }.depth(), 1);
+
+        let mut deep = table!{ "leaf" => true };
+        for _ in 0..1000 {
+            deep = table!{ "next" => deep };
+        }
+        assert_eq!(deep.deep_len(), 1);
+        assert_eq!(deep.depth(), 1001);
+    }
+
+    #[test]
+    fn test_deep_map_values() {
+        let shared = table!{ "name" => "Sven", "tags" => vec![Value::str("a")] };
+        let a = table!{
+            "pos" => table!{ "x" => 1.25, "y" => table!{ "z" => 2.5 } },
+            "list" => vec![Value::f64(0.75), Value::table(table!{ "w" => 3.5 })],
+            "info" => shared.clone(),
+            "n" => 1usize,
+            "empty" => Table::new(),
+        };
+        let b = a.deep_map_values(|val| match *val {
+            Value::F64(x) => Value::f64(x.floor()),
+            _ => val.clone(),
+        });
+        assert_eq!(b, table!{
+            "pos" => table!{ "x" => 1.0, "y" => table!{ "z" => 2.0 } },
+            "list" => vec![Value::f64(0.0), Value::table(table!{ "w" => 3.0 })],
+            "info" => shared,
+            "n" => 1usize,
+            "empty" => Table::new(),
+        });
+        match (&a["info"], &b["info"]) {
+            (&Value::Table(ref x), &Value::Table(ref y)) => assert!(Arc::ptr_eq(x, y)),
+            _ => unreachable!(),
+        }
+        assert!(a.deep_map_values(|val| val.clone()) == a);
+        let strings = a.deep_map_values(|val| Value::from(val.to_string()));
+        assert_eq!(strings.get_path("n"), Some(&Value::str("1")));
+        assert_eq!(strings.get_path("info.tags.0"), Some(&Value::str("a")));
+        assert_eq!(Table::new().deep_map_values(|_| Value::Null), Table::new());
+
+        let mut deep = table!{ "leaf" => 1usize };
+        for _ in 0..1000 {
+            deep = table!{ "next" => deep };
+        }
+        let deep = deep.deep_map_values(|val| val + &Value::Usize(1));
+        assert_eq!(deep.deep_len(), 1);
+        assert_eq!(deep.depth(), 1001);
+        deep.walk(1001, |_, val| assert_eq!(*val, Value::Usize(2))).unwrap();
+    }
+
+    #[test]
+    fn test_deep_map_keys() {
+        let shared = table!{
+            "x" => 1usize,
+            0usize => vec![Value::table(table!{ "a" => 2 })],
+        };
+        let a = table!{
+            "Name" => "Sven",
+            "name" => "Other",
+            "POS" => table!{ "X" => 1.0, "Y" => 2.0 },
+            "list" => vec![Value::table(table!{ "A" => 1usize })],
+            "keep" => shared.clone(),
+            1usize => "one",
+        };
+        let lower = |key: &Value| match key.as_str() {
+            Some(text) => Value::from(text.to_lowercase()),
+            None => key.clone(),
+        };
+        let b = a.deep_map_keys(lower);
+        // "name" sorts after "Name", so its entry wins the collision.
+        assert_eq!(b, table!{
+            "name" => "Other",
+            "pos" => table!{ "x" => 1.0, "y" => 2.0 },
+            "list" => vec![Value::table(table!{ "a" => 1usize })],
+            "keep" => shared,
+            1usize => "one",
+        });
+        match (&a["keep"], &b["keep"]) {
+            (&Value::Table(ref x), &Value::Table(ref y)) => assert!(Arc::ptr_eq(x, y)),
+            _ => unreachable!(),
+        }
+        assert_eq!(a.deep_map_keys(|key| key.clone()), a);
+        let one = a.deep_map_keys(|_| Value::Usize(0));
+        assert_eq!(one.len(), 1);
+        assert_eq!(one[0], Value::str("Other"));
+
+        let mut deep = table!{ "LEAF" => true };
+        for _ in 0..1000 {
+            deep = table!{ "NEXT" => deep };
+        }
+        let deep = deep.deep_map_keys(lower);
+        assert_eq!(deep.depth(), 1001);
+        assert!(deep.contains_key(&Value::str("next")));
+    }
+
+    #[test]
+    fn test_flatten() {
+        let a = table!{
+            "a" => table!{ "b" => 1, "c" => table!{ "d" => true } },
+            "pos" => table!{ 0 => 1.0, 1 => 2.0 },
+            "empty" => Table::new(),
+            "e" => "f",
+        };
+        let flat = table!{
+            "a.b" => 1,
+            "a.c.d" => true,
+            "pos.0" => 1.0,
+            "pos.1" => 2.0,
+            "empty" => Table::new(),
+            "e" => "f",
+        };
+        assert_eq!(a.flatten(), flat);
+        assert_eq!(flat.unflatten(), Ok(a.clone()));
+        assert_eq!(a.flatten_with("__")["a__c__d"], Value::Bool(true));
+        assert_eq!(a.flatten_with("__").unflatten_with("__"), Ok(a));
+    }
+
+    #[test]
+    fn test_flatten_collision() {
+        // Each table has its own hash seed, so repeat to vary the order.
+        for _ in 0..20 {
+            let a = table!{ "a.b" => 1, "a" => table!{ "b" => 2 } };
+            assert_eq!(a.flatten(), table!{ "a.b" => 1 });
+            let b = table!{
+                1usize => "usize", "1" => "str", 'a' => "char", "a" => "str",
+            };
+            assert_eq!(b.flatten(), table!{ "1" => "str", "a" => "str" });
+        }
+    }
+
+    #[test]
+    fn test_unflatten_conflict() {
+        let a = table!{ "a.b" => 1, "a.b.c" => 2 };
+        assert_eq!(a.unflatten(), Err(KeyConflict { key: Value::str("a.b.c") }));
+        let b = table!{ "0" => 1, 0 => 2 };
+        assert!(b.unflatten().is_err());
+        assert_eq!(table!{ "a.01" => 1 }.unflatten(),
+            Ok(table!{ "a" => table!{ "01" => 1 } }));
+    }
+}